@@ -1,8 +1,160 @@
+//! errors that stop a file from loading, and warnings about parts of a file that were skipped
+
+use std::cell::RefCell;
+use std::fmt;
+
+/// what went wrong, `TiledError` holds where it happened
 #[derive(Debug)]
-pub enum TiledError {
-    FileReadError(String, String),
+pub enum ErrorKind {
+    FileReadError(String),
+    FileWriteError(String),
+    WriteError(String),
     ParseError(String),
     ParseBytesError(),
-    UnsupportedType(),
+    /// a compression, encoding, order or property type this crate doesn't know
+    UnsupportedType(String),
     MissingPoint(),
 }
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::FileReadError(e) => write!(f, "could not read file: {}", e),
+            ErrorKind::FileWriteError(e) => write!(f, "could not write file: {}", e),
+            ErrorKind::WriteError(e) => write!(f, "could not write xml: {}", e),
+            ErrorKind::ParseError(e) => write!(f, "{}", e),
+            ErrorKind::ParseBytesError() => write!(f, "a value could not be parsed"),
+            ErrorKind::UnsupportedType(t) => write!(f, "unsupported type {:?}", t),
+            ErrorKind::MissingPoint() => write!(f, "a point was missing a coordinate"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TiledError {
+    pub kind: ErrorKind,
+    /// the file being read or written
+    pub file: Option<String>,
+    /// byte offset into `file` of the element being read
+    pub byte: Option<usize>,
+    /// line and column in `file`, counting from 1
+    pub line: Option<(usize, usize)>,
+    /// name of the element being read
+    pub element: Option<String>,
+}
+
+impl TiledError {
+    pub fn new(kind: ErrorKind) -> TiledError {
+        TiledError { kind, file: None, byte: None, line: None, element: None }
+    }
+
+    pub(crate) fn parse(message: String) -> TiledError {
+        Self::new(ErrorKind::ParseError(message))
+    }
+
+    /// context is only added where it is missing, so the innermost element is kept
+    pub(crate) fn in_element(mut self, element: &str, byte: Option<usize>) -> TiledError {
+        if self.element.is_none() && !element.is_empty() {
+            self.element = Some(element.to_string());
+        }
+        if self.byte.is_none() {
+            self.byte = byte;
+        }
+        self
+    }
+
+    /// add the file the error happened in, working out the line from `text`,
+    /// errors from files referenced by this one keep their own file
+    pub(crate) fn in_file(mut self, file: &str, text: &str) -> TiledError {
+        if self.file.is_some() {
+            return self;
+        }
+        self.file = Some(file.to_string());
+        if let (None, Some(byte)) = (self.line, self.byte) {
+            let before = &text.as_bytes()[..byte.min(text.len())];
+            let line_start = before.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1);
+            self.line = Some((
+                before.iter().filter(|b| **b == b'\n').count() + 1,
+                before.len() - line_start + 1,
+            ));
+        }
+        self
+    }
+}
+
+impl From<ErrorKind> for TiledError {
+    fn from(kind: ErrorKind) -> TiledError {
+        TiledError::new(kind)
+    }
+}
+
+impl fmt::Display for TiledError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}", file)?;
+            if let Some((line, column)) = self.line {
+                write!(f, ":{}:{}", line, column)?;
+            }
+            write!(f, ": ")?;
+        }
+        if let Some(element) = &self.element {
+            write!(f, "in {}: ", element)?;
+        }
+        write!(f, "{}", self.kind)
+    }
+}
+
+impl std::error::Error for TiledError {}
+
+/// something in a file the parser didn't understand and skipped
+#[derive(Debug, Clone)]
+pub struct Warning {
+    pub file: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "{}: {}", file, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+thread_local! {
+    static WARNINGS: RefCell<Vec<Warning>> = const { RefCell::new(Vec::new()) };
+}
+
+pub(crate) fn warn(message: String) {
+    WARNINGS.with(|w| w.borrow_mut().push(Warning { file: None, message }));
+}
+
+pub(crate) fn warning_count() -> usize {
+    WARNINGS.with(|w| w.borrow().len())
+}
+
+/// mark warnings from `start` on that don't have a file yet as coming from `file`
+pub(crate) fn set_warning_file(start: usize, file: &str) {
+    WARNINGS.with(|w| {
+        for warning in w.borrow_mut().iter_mut().skip(start) {
+            if warning.file.is_none() {
+                warning.file = Some(file.to_string());
+            }
+        }
+    });
+}
+
+pub(crate) fn take_warnings_from(start: usize) -> Vec<Warning> {
+    WARNINGS.with(|w| {
+        let mut w = w.borrow_mut();
+        let start = start.min(w.len());
+        w.split_off(start)
+    })
+}
+
+/// warnings collected on this thread that no `Map` has taken,
+/// such as those from reading a `Tileset` on its own
+pub fn take_warnings() -> Vec<Warning> {
+    take_warnings_from(0)
+}
@@ -0,0 +1,79 @@
+use super::{Group, LayerData, LayerNode, Map, Properties, Colour, TiledError, helper::*};
+
+use quick_xml::events::BytesStart;
+use quick_xml::events::attributes::Attribute;
+use quick_xml::reader::Reader;
+
+impl Group {
+    pub fn new(attribs : Vec<Attribute>) -> Result<Group, TiledError> {
+        let mut group = Group { info: LayerData::new(), props: Properties::blank(), layers: Vec::new() };
+        for a in attribs {
+            if let Some(()) = group.info.handle_attrib(&a)? {
+                unknown_attrib("group", &a);
+            }
+        }
+        Ok(group)
+    }
+
+    /// read a group and the layers in it, which are added to `map`'s lists
+    pub(crate) fn parse(attribs : Vec<Attribute>, map: &mut Map, reader: &mut Reader<&[u8]>) -> Result<Group, TiledError> {
+        let mut parser = GroupParser { group: Group::new(attribs)?, map };
+        parse_xml(&mut parser, reader)?;
+        Ok(parser.group)
+    }
+}
+
+struct GroupParser<'a> {
+    group: Group,
+    map: &'a mut Map,
+}
+
+impl HandleXml for GroupParser<'_> {
+    fn start(&mut self, e : &BytesStart, reader: &mut Reader<&[u8]>) -> Result<(), TiledError> {
+        match e.name().as_ref() {
+            b"properties" => parse_xml(&mut self.group.props, reader)?,
+            b"layer" | b"objectgroup" | b"imagelayer" | b"group" => {
+                let node = self.map.parse_layer(e, reader)?;
+                self.group.layers.push(node);
+            },
+            _ => unknown_tag(Self::self_tag(), e),
+        }
+        Ok(())
+    }
+    fn empty(&mut self, e : &BytesStart) -> Result<(), TiledError> {
+        match e.name().as_ref() {
+            b"group" => self.group.layers.push(LayerNode::Group(Box::new(Group::new(collect_attribs(e)?)?))),
+            _ => unknown_tag(Self::self_tag(), e),
+        }
+        Ok(())
+    }
+    fn self_tag() -> &'static str {
+        "group"
+    }
+}
+
+fn tint(c: &Colour, group: &Colour) -> Colour {
+    Colour {
+        r: c.r * group.r / 255,
+        g: c.g * group.g / 255,
+        b: c.b * group.b / 255,
+        a: c.a * group.a / 255,
+    }
+}
+
+impl LayerData {
+    /// the settings a layer ends up drawn with inside a group,
+    /// offsets add up while opacity, tint and parallax multiply
+    pub fn in_group(&self, group: &LayerData) -> LayerData {
+        let mut info = self.clone();
+        info.visible = self.visible && group.visible;
+        info.locked = self.locked || group.locked;
+        info.opacity = self.opacity * group.opacity;
+        info.tint = tint(&self.tint, &group.tint);
+        info.parallax.x = self.parallax.x * group.parallax.x;
+        info.parallax.y = self.parallax.y * group.parallax.y;
+        info.offset.x = self.offset.x + group.offset.x;
+        info.offset.y = self.offset.y + group.offset.y;
+        info
+    }
+}
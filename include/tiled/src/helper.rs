@@ -7,55 +7,96 @@ use quick_xml::events::{BytesStart, Event, BytesText};
 use quick_xml::reader::Reader;
 
 use super::{LayerData, Colour};
-use super::error::TiledError;
+use super::error::{self, TiledError, ErrorKind};
 
 use geometry::Vec2;
 
 pub fn read_file_to_string(filename : &str) -> Result<String, TiledError> {
-    let mut file = match File::open(filename) {
-        Ok(f) => f,
-        Err(e) => {
-            return Err(TiledError::FileReadError(filename.to_string(), e.to_string()));
-        }
+    let read_error = |e: std::io::Error| {
+        let mut err = TiledError::new(ErrorKind::FileReadError(e.to_string()));
+        err.file = Some(filename.to_string());
+        err
     };
-    
+    let mut file = File::open(filename).map_err(read_error)?;
     let mut text = String::new();
-    match file.read_to_string(&mut text) {
-        Ok(_) => (),
-        Err(e) => {
-            return Err(TiledError::FileReadError(filename.to_string(), e.to_string()));
-        }
-    };
+    file.read_to_string(&mut text).map_err(read_error)?;
     Ok(text)
 }
 
+/// read a file and parse its text, errors and warnings from
+/// parsing are marked with the file and where in it they happened
+pub fn parse_file<T>(filename : &str, parse : impl FnOnce(&str) -> Result<T, TiledError>) -> Result<T, TiledError> {
+    let text = read_file_to_string(filename)?;
+    let warnings_start = error::warning_count();
+    let result = parse(&text).map_err(|e| e.in_file(filename, &text));
+    error::set_warning_file(warnings_start, filename);
+    result
+}
+
+/// the path of `file` relative to the directory `dir`, with `.` and `..` folded away where they can be
+pub fn join_path(dir : &str, file : &str) -> String {
+    let full = if file.starts_with('/') || dir.is_empty() {
+        file.to_string()
+    } else {
+        format!("{}/{}", dir, file)
+    };
+    let mut parts: Vec<&str> = Vec::new();
+    for part in full.split('/') {
+        match part {
+            "" | "." => (),
+            ".." if parts.last().is_some_and(|p| *p != "..") => { parts.pop(); },
+            p => parts.push(p),
+        }
+    }
+    let joined = parts.join("/");
+    if full.starts_with('/') { format!("/{}", joined) } else { joined }
+}
+
+/// note an attribute the parser skipped
+pub fn unknown_attrib(element : &str, a : &Attribute) {
+    error::warn(format!("unrecognized attribute {:?} on {}", String::from_utf8_lossy(a.key.as_ref()), element));
+}
+
+/// note an element the parser skipped
+pub fn unknown_tag(parent : &str, e : &BytesStart) {
+    error::warn(format!("unrecognized element {:?} in {}", String::from_utf8_lossy(e.name().as_ref()), parent));
+}
+
+/// whether a file should be read with the json parser rather than as xml
+pub fn is_json(filename : &str) -> bool {
+    [".tmj", ".tsj", ".tj", ".json"].iter().any(|ext| filename.ends_with(ext))
+}
+
 pub fn get_string<'a>(data : &'a std::borrow::Cow<[u8]>) -> Result<&'a str, TiledError>  {
     match core::str::from_utf8(data) {
         Ok(v) => Ok(v),
-        Err(_) => Err(TiledError::ParseBytesError())
+        Err(_) => Err(TiledError::new(ErrorKind::ParseBytesError()))
     }
 }
 
 pub fn get_value<T : std::str::FromStr>(data : &std::borrow::Cow<[u8]>)  -> Result<T, TiledError> {
     match get_string(data)?.parse() {
         Ok(v) => Ok(v),
-        Err(_) => Err(TiledError::ParseBytesError()),
+        Err(_) => Err(TiledError::new(ErrorKind::ParseBytesError())),
     }
 }
 
 fn hex_from_str(txt : &str) -> Result<u32, TiledError> {
     match u32::from_str_radix(txt, 16) {
         Ok(v) => Ok(v),
-        Err(_) => Err(TiledError::ParseError(String::from("hex value could not be parsed to integer"))),
+        Err(_) => Err(TiledError::parse(String::from("hex value could not be parsed to integer"))),
     }
 }
 
 pub fn get_colour(data : &std::borrow::Cow<[u8]>)  -> Result<Colour, TiledError> {
+    colour_from_str(get_string(data)?)
+}
+
+pub fn colour_from_str(txt : &str) -> Result<Colour, TiledError> {
     let mut col = Colour { r: 255, g: 255, b: 255, a: 255};
-    let txt = get_string(data)?;
     let txt = match txt.strip_prefix("#") {
         Some(txt) => txt,
-        None => { return Err(TiledError::ParseError(String::from("colour value didnt start with hash"))); },
+        None => { return Err(TiledError::parse(String::from("colour value didnt start with hash"))); },
     };
     let mut i = 0;
     if txt.len() == 6 {
@@ -64,7 +105,7 @@ pub fn get_colour(data : &std::borrow::Cow<[u8]>)  -> Result<Colour, TiledError>
         col.a = hex_from_str(&txt[i..i+2])?;
         i += 2;
     } else {
-        return Err(TiledError::ParseError(String::from("colour value didn't have 6 or 8 chars")));
+        return Err(TiledError::parse(String::from("colour value didn't have 6 or 8 chars")));
     }
     col.r = hex_from_str(&txt[i..i+2])?;
     i += 2;
@@ -78,7 +119,7 @@ pub fn get_colour(data : &std::borrow::Cow<[u8]>)  -> Result<Colour, TiledError>
     match get_string(data)? {
         "true" => Ok(true),
         "false" => Ok(false),
-        _ => Err(TiledError::ParseBytesError()),
+        _ => Err(TiledError::new(ErrorKind::ParseBytesError())),
     }
 }*/
     
@@ -88,8 +129,8 @@ pub fn collect_attribs<'a>(byte_start: &'a BytesStart) -> Result<Vec::<Attribute
         attribs.push( match a {
             Ok(a) => a,
             Err(e) => {
-                return Err(TiledError::ParseError(
-                    format!("tag name: {:?} error: {}", byte_start.name(), e.to_string())
+                return Err(TiledError::parse(
+                    format!("bad attribute: {}", e)
                 ));
             }
         });
@@ -112,9 +153,13 @@ pub trait HandleXml {
 
 pub fn parse_xml<T : HandleXml>(this: &mut T, reader: &mut Reader::<&[u8]>) -> Result<(), TiledError> {
     loop {
+        let pos = reader.buffer_position();
+        let in_element = |e: &BytesStart, err: TiledError| {
+            err.in_element(&String::from_utf8_lossy(e.name().as_ref()), Some(pos))
+        };
         match reader.read_event() {
             Err(e) => {
-                return Err(TiledError::ParseError(e.to_string()));
+                return Err(TiledError::parse(e.to_string()).in_element(T::self_tag(), Some(pos)));
             },
             Ok(Event::Eof) => break,
             Ok(Event::Start(e)) => {
@@ -122,7 +167,7 @@ pub fn parse_xml<T : HandleXml>(this: &mut T, reader: &mut Reader::<&[u8]>) -> R
                 // e.name().as_ref().read_to_string(&mut s).unwrap();
                 // println!("start tag: {}", s);
                 
-                <T as HandleXml>::start(this, &e, reader)?;
+                <T as HandleXml>::start(this, &e, reader).map_err(|err| in_element(&e, err))?;
             },
             Ok(Event::End(e)) => {
                // let mut s = String::new();
@@ -133,14 +178,14 @@ pub fn parse_xml<T : HandleXml>(this: &mut T, reader: &mut Reader::<&[u8]>) -> R
                 }
             }
             Ok(Event::Text(e)) => {
-                <T as HandleXml>::text(this, &e)?;
+                <T as HandleXml>::text(this, &e).map_err(|err| err.in_element(T::self_tag(), Some(pos)))?;
             }
             Ok(Event::Empty(e)) => {
                 //  let mut s = String::new();
                 //  e.name().as_ref().read_to_string(&mut s).unwrap();
                 //  println!("empty tag: {}", s);
                 
-                <T as HandleXml>::empty(this, &e)?;
+                <T as HandleXml>::empty(this, &e).map_err(|err| in_element(&e, err))?;
             }
             _ => (),
         } 
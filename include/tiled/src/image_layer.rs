@@ -3,7 +3,7 @@ use super::{ImageLayer, TiledError, HandleXml, helper::*, LayerData, Properties}
 use quick_xml::{events::{BytesStart, attributes::Attribute}, Reader};
 
 impl ImageLayer {
-    fn blank() -> ImageLayer {
+    pub(crate) fn blank() -> ImageLayer {
         ImageLayer {
             image_path: String::from(""),
             width: 0,
@@ -27,7 +27,7 @@ impl ImageLayer {
                 match a.key.as_ref() {
                     b"repeatx" => self.repeat_x = get_value::<i32>(&a.value)? == 1,
                     b"repeaty" => self.repeat_y = get_value::<i32>(&a.value)? == 1,
-                    _ => println!("warning: unrecognized atrribute {:?}", a.key),
+                    _ => unknown_attrib("imagelayer", &a),
                 }
             }
         }
@@ -40,7 +40,7 @@ impl ImageLayer {
                 b"source" => self.image_path = get_string(&a.value)?.to_string(),
                 b"width" => self.width = get_value(&a.value)?,
                 b"height" => self.height = get_value(&a.value)?,
-                _ => println!("warning: unrecognized atrribute {:?}", a.key),
+                _ => unknown_attrib("image", &a),
             }
         }
         Ok(())
@@ -52,14 +52,14 @@ impl HandleXml for ImageLayer {
     fn empty(&mut self, e : &BytesStart) -> Result<(), TiledError> {
         match e.name().as_ref() {
             b"image" => self.parse_image_attributes(collect_attribs(&e)?)?,
-            _ => println!("unrecognized empty tag {:?}", e.name()),
+            _ => unknown_tag(Self::self_tag(), e),
         }
         Ok(())
     }
     fn start(&mut self, e : &BytesStart, reader: &mut Reader<&[u8]>) -> Result<(), TiledError> {
         match e.name().as_ref() {
             b"properties" => parse_xml(&mut self.props, reader)?,
-            _ => println!("unrecognized tag {:?}", e.name()),
+            _ => unknown_tag(Self::self_tag(), e),
         }
         Ok(())
     }
@@ -0,0 +1,513 @@
+//! reads tiled's json formats (.tmj maps, .tsj tilesets and .tj templates)
+//! into the same structs the xml parser fills in
+
+use super::{Map, Layer, LayerKind, LayerNode, Group, Chunk, LayerTiles, LayerData, ObjGroup, Obj, Poly, Text, ImageLayer};
+use super::{Tileset, Tile, Frame, Properties, ClassProperty, Colour, Orientation, RenderOrder, StaggerAxis, StaggerIndex};
+use super::{TextHorizontalAlign, TextVerticalAlign, Templates, Terrain, WangSet, WangSetType, WangColour, WangId};
+use super::template::TemplateFile;
+use super::error::{self, TiledError, ErrorKind};
+use super::helper::{colour_from_str, join_path};
+use super::layer::decode_base64;
+use super::wang::tile_index;
+
+use geometry::Vec2;
+use serde_json::Value;
+
+type Object = serde_json::Map<String, Value>;
+
+fn parse(text: &str) -> Result<Value, TiledError> {
+    match serde_json::from_str(text) {
+        Ok(v) => Ok(v),
+        Err(e) => {
+            let mut err = TiledError::parse(format!("invalid json: {}", e));
+            err.line = Some((e.line(), e.column()));
+            Err(err)
+        },
+    }
+}
+
+/// name the json object an error happened in by its kind and id, ie `layer 3`
+fn in_object<'a>(what: &'a str, o: &'a Object) -> impl Fn(TiledError) -> TiledError + 'a {
+    move |e| match o.get("id") {
+        Some(id) => e.in_element(&format!("{} {}", what, id), None),
+        None => e.in_element(what, None),
+    }
+}
+
+fn as_object<'a>(v: &'a Value, what: &str) -> Result<&'a Object, TiledError> {
+    match v.as_object() {
+        Some(o) => Ok(o),
+        None => Err(TiledError::parse(format!("expected {} to be a json object", what))),
+    }
+}
+
+fn wrong_type(key: &str) -> TiledError {
+    TiledError::parse(format!("json field {:?} had the wrong type", key))
+}
+
+/// the fields below return `default` when the field is missing
+/// and an error when it has the wrong type
+fn num(o: &Object, key: &str, default: f64) -> Result<f64, TiledError> {
+    match o.get(key) {
+        Some(v) => v.as_f64().ok_or_else(|| wrong_type(key)),
+        None => Ok(default),
+    }
+}
+
+fn int(o: &Object, key: &str, default: i64) -> Result<i64, TiledError> {
+    match o.get(key) {
+        Some(v) => v.as_i64().ok_or_else(|| wrong_type(key)),
+        None => Ok(default),
+    }
+}
+
+fn flag(o: &Object, key: &str, default: bool) -> Result<bool, TiledError> {
+    match o.get(key) {
+        Some(v) => v.as_bool().ok_or_else(|| wrong_type(key)),
+        None => Ok(default),
+    }
+}
+
+fn string<'a>(o: &'a Object, key: &str) -> Result<Option<&'a str>, TiledError> {
+    match o.get(key) {
+        Some(v) => v.as_str().map(Some).ok_or_else(|| wrong_type(key)),
+        None => Ok(None),
+    }
+}
+
+fn list<'a>(o: &'a Object, key: &str) -> Result<&'a [Value], TiledError> {
+    match o.get(key) {
+        Some(v) => v.as_array().map(|a| a.as_slice()).ok_or_else(|| wrong_type(key)),
+        None => Ok(&[]),
+    }
+}
+
+/// versions are strings in newer files and numbers in older ones
+fn version(o: &Object, key: &str) -> String {
+    match o.get(key) {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Number(n)) => n.to_string(),
+        _ => String::new(),
+    }
+}
+
+pub fn parse_map(text: &str, path: &str) -> Result<Map, TiledError> {
+    let v = parse(text)?;
+    let o = as_object(&v, "map")?;
+    let mut map = Map::blank_map(path.to_string());
+    map.width = int(o, "width", 0)? as u32;
+    map.height = int(o, "height", 0)? as u32;
+    map.tile_width = int(o, "tilewidth", 0)? as u32;
+    map.tile_height = int(o, "tileheight", 0)? as u32;
+    map.infinite = flag(o, "infinite", false)?;
+    if let Some(orientation) = string(o, "orientation")? {
+        map.orientation = Orientation::new(orientation)?;
+    }
+    if let Some(axis) = string(o, "staggeraxis")? {
+        map.stagger_axis = StaggerAxis::new(axis)?;
+    }
+    if let Some(index) = string(o, "staggerindex")? {
+        map.stagger_index = StaggerIndex::new(index)?;
+    }
+    map.hex_side_length = int(o, "hexsidelength", 0)? as u32;
+    if let Some(order) = string(o, "renderorder")? {
+        map.metadata.render_order = RenderOrder::new(order)?;
+    }
+    map.metadata.version = version(o, "version");
+    map.metadata.tiled_version = version(o, "tiledversion");
+    map.metadata.next_layer_id = int(o, "nextlayerid", 0)? as u32;
+    map.metadata.next_object_id = int(o, "nextobjectid", 0)? as u32;
+
+    for ts in list(o, "tilesets")? {
+        let ts = as_object(ts, "tileset")?;
+        let ts = parse_map_tileset(ts, path).map_err(in_object("tileset", ts))?;
+        map.add_tileset(ts);
+    }
+    map.layer_tree = parse_layers(&mut map, list(o, "layers")?)?;
+    map.resolve_template_tilesets()?;
+    map.set_rect();
+    Ok(map)
+}
+
+/// add each layer to the map's list for its kind, giving back their tree
+fn parse_layers(map: &mut Map, layers: &[Value]) -> Result<Vec<LayerNode>, TiledError> {
+    let mut nodes = Vec::new();
+    for l in layers {
+        let o = as_object(l, "layer")?;
+        nodes.push(match string(o, "type")?.unwrap_or("") {
+            "tilelayer" => {
+                map.layers.push(parse_tile_layer(o).map_err(in_object("layer", o))?);
+                LayerNode::Layer(LayerKind::Tile, map.layers.len() - 1)
+            },
+            "objectgroup" => {
+                let og = parse_obj_group(o, &map.path, &mut map.templates).map_err(in_object("objectgroup", o))?;
+                map.obj_groups.push(og);
+                LayerNode::Layer(LayerKind::Objects, map.obj_groups.len() - 1)
+            },
+            "imagelayer" => {
+                map.img_layers.push(parse_image_layer(o).map_err(in_object("imagelayer", o))?);
+                LayerNode::Layer(LayerKind::Image, map.img_layers.len() - 1)
+            },
+            "group" => {
+                let mut group = Group { info: LayerData::new(), props: Properties::blank(), layers: Vec::new() };
+                parse_layer_data(&mut group.info, o)?;
+                parse_properties(&mut group.props, o)?;
+                group.layers = parse_layers(map, list(o, "layers")?)?;
+                LayerNode::Group(Box::new(group))
+            },
+            t => {
+                error::warn(format!("unrecognized layer type {:?}", t));
+                continue;
+            },
+        });
+    }
+    Ok(nodes)
+}
+
+fn parse_layer_data(info: &mut LayerData, o: &Object) -> Result<(), TiledError> {
+    info.id = int(o, "id", info.id as i64)? as u32;
+    info.name = string(o, "name")?.unwrap_or("").to_string();
+    info.visible = flag(o, "visible", info.visible)?;
+    info.locked = flag(o, "locked", info.locked)?;
+    info.opacity = num(o, "opacity", info.opacity)?;
+    if let Some(c) = string(o, "color")? {
+        info.colour = colour_from_str(c)?;
+    }
+    if let Some(c) = string(o, "tintcolor")? {
+        info.tint = colour_from_str(c)?;
+    }
+    info.index_draw_order = string(o, "draworder")? == Some("index");
+    info.offset.x = num(o, "offsetx", info.offset.x)?;
+    info.offset.y = num(o, "offsety", info.offset.y)?;
+    info.parallax.x = num(o, "parallaxx", info.parallax.x)?;
+    info.parallax.y = num(o, "parallaxy", info.parallax.y)?;
+    Ok(())
+}
+
+/// tile ids are an array, or a base64 string when the layer has an encoding
+fn tile_data(o: &Object, compression: &str) -> Result<LayerTiles, TiledError> {
+    match o.get("data") {
+        Some(Value::Array(ids)) => ids.iter()
+            .map(|id| id.as_u64().map(|id| id as u32).ok_or_else(|| wrong_type("data")))
+            .collect(),
+        Some(Value::String(data)) => decode_base64(data, compression),
+        Some(_) => Err(wrong_type("data")),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn parse_tile_layer(o: &Object) -> Result<Layer, TiledError> {
+    let mut layer = Layer::blank();
+    parse_layer_data(&mut layer.info, o)?;
+    parse_properties(&mut layer.props, o)?;
+    layer.width = int(o, "width", 0)? as i32;
+    layer.height = int(o, "height", 0)? as i32;
+    let compression = string(o, "compression")?.unwrap_or("");
+    layer.tiles = tile_data(o, compression)?;
+    for c in list(o, "chunks")? {
+        let c = as_object(c, "chunk")?;
+        layer.chunks.push(Chunk {
+            x: int(c, "x", 0)? as i32,
+            y: int(c, "y", 0)? as i32,
+            width: int(c, "width", 0)? as i32,
+            height: int(c, "height", 0)? as i32,
+            tiles: tile_data(c, compression)?,
+        });
+    }
+    Ok(layer)
+}
+
+fn parse_obj_group(o: &Object, path: &str, templates: &mut Templates) -> Result<ObjGroup, TiledError> {
+    let mut og = ObjGroup::blank();
+    og.path = path.to_string();
+    parse_layer_data(&mut og.info, o)?;
+    parse_properties(&mut og.props, o)?;
+    for obj in list(o, "objects")? {
+        let obj = as_object(obj, "object")?;
+        og.objs.push(parse_object(obj, path, templates).map_err(in_object("object", obj))?);
+    }
+    og.sort_objs();
+    Ok(og)
+}
+
+fn parse_object(o: &Object, path: &str, templates: &mut Templates) -> Result<Obj, TiledError> {
+    let mut obj = match string(o, "template")? {
+        Some(template) => {
+            let mut obj = Obj::from_template(template, path, templates)?;
+            obj.template = Some(template.to_string());
+            obj
+        },
+        None => Obj::blank(),
+    };
+    parse_object_fields(&mut obj, o)?;
+    Ok(obj)
+}
+
+/// fields missing from `o` are left as they are, so an object can be layered over its template
+fn parse_object_fields(obj: &mut Obj, o: &Object) -> Result<(), TiledError> {
+    obj.info.id = int(o, "id", obj.info.id as i64)? as u32;
+    if let Some(name) = string(o, "name")? {
+        obj.info.name = name.to_string();
+    }
+    // renamed to class in tiled 1.9
+    if let Some(type_name) = string(o, "type")?.or(string(o, "class")?) {
+        obj.info.type_name = type_name.to_string();
+    }
+    obj.info.visible = flag(o, "visible", obj.info.visible)?;
+    obj.rect.x = num(o, "x", obj.rect.x)?;
+    obj.rect.y = num(o, "y", obj.rect.y)?;
+    obj.rect.w = num(o, "width", obj.rect.w)?;
+    obj.rect.h = num(o, "height", obj.rect.h)?;
+    obj.rotation = num(o, "rotation", obj.rotation)?;
+    if o.contains_key("gid") {
+        obj.gid = int(o, "gid", 0)? as u32;
+        obj.template_tileset = None;
+    }
+    obj.point = flag(o, "point", obj.point)?;
+    obj.ellipse = flag(o, "ellipse", obj.ellipse)?;
+    parse_properties(&mut obj.props, o)?;
+    for (key, closed) in [("polyline", false), ("polygon", true)] {
+        if o.contains_key(key) {
+            let mut poly = Poly::blank();
+            poly.closed = closed;
+            for p in list(o, key)? {
+                let p = as_object(p, "point")?;
+                poly.points.push(Vec2::new(num(p, "x", 0.0)?, num(p, "y", 0.0)?));
+            }
+            obj.poly = Some(Box::new(poly));
+        }
+    }
+    if let Some(text) = o.get("text") {
+        obj.text = Some(Box::new(parse_text(as_object(text, "text")?)?));
+    }
+    Ok(())
+}
+
+fn parse_text(o: &Object) -> Result<Text, TiledError> {
+    let mut text = Text::blank();
+    text.text = string(o, "text")?.unwrap_or("").to_string();
+    if let Some(family) = string(o, "fontfamily")? {
+        text.font_family = family.to_string();
+    }
+    text.pixel_size = int(o, "pixelsize", text.pixel_size as i64)? as u32;
+    text.wrap = flag(o, "wrap", false)?;
+    text.bold = flag(o, "bold", false)?;
+    text.italic = flag(o, "italic", false)?;
+    if let Some(align) = string(o, "halign")? {
+        text.horizontal_align = TextHorizontalAlign::new(align)?;
+    }
+    if let Some(align) = string(o, "valign")? {
+        text.vertical_align = TextVerticalAlign::new(align)?;
+    }
+    if let Some(c) = string(o, "color")? {
+        text.colour = colour_from_str(c)?;
+    }
+    Ok(text)
+}
+
+/// read a .tj template file
+pub fn parse_template(t: &mut TemplateFile, text: &str) -> Result<(), TiledError> {
+    let v = parse(text)?;
+    let o = as_object(&v, "template")?;
+    if let Some(ts) = o.get("tileset") {
+        let ts = as_object(ts, "tileset")?;
+        t.tileset = Some((
+            int(ts, "firstgid", 1)? as u32,
+            string(ts, "source")?.unwrap_or("").to_string(),
+        ));
+    }
+    match o.get("object") {
+        Some(template) => parse_object_fields(&mut t.obj, as_object(template, "template object")?),
+        None => Err(TiledError::parse(String::from("template had no object"))),
+    }
+}
+
+fn parse_image_layer(o: &Object) -> Result<ImageLayer, TiledError> {
+    let mut img_layer = ImageLayer::blank();
+    parse_layer_data(&mut img_layer.info, o)?;
+    parse_properties(&mut img_layer.props, o)?;
+    img_layer.image_path = string(o, "image")?.unwrap_or("").to_string();
+    img_layer.width = int(o, "imagewidth", 0)? as u32;
+    img_layer.height = int(o, "imageheight", 0)? as u32;
+    img_layer.repeat_x = flag(o, "repeatx", false)?;
+    img_layer.repeat_y = flag(o, "repeaty", false)?;
+    Ok(img_layer)
+}
+
+fn parse_properties(props: &mut Properties, o: &Object) -> Result<(), TiledError> {
+    for p in list(o, "properties")? {
+        let p = as_object(p, "property")?;
+        let name = string(p, "name")?.unwrap_or("").to_string();
+        let value = p.get("value").unwrap_or(&Value::Null);
+        let err = || wrong_type("value");
+        match string(p, "type")?.unwrap_or("string") {
+            "bool" => { props.booleans.insert(name, value.as_bool().ok_or_else(err)?); },
+            "int" => { props.integers.insert(name, value.as_i64().ok_or_else(err)?); },
+            "float" => { props.floats.insert(name, value.as_f64().ok_or_else(err)?); },
+            "string" => { props.strings.insert(name, value.as_str().ok_or_else(err)?.to_string()); },
+            "color" => {
+                // an unset colour is saved as an empty string
+                let colour = match value.as_str().ok_or_else(err)? {
+                    "" => Colour { r: 0, g: 0, b: 0, a: 0 },
+                    c => colour_from_str(c)?,
+                };
+                props.colours.insert(name, colour);
+            },
+            "file" => { props.files.insert(name, value.as_str().ok_or_else(err)?.to_string()); },
+            "object" => { props.objects.insert(name, value.as_u64().ok_or_else(err)? as u32); },
+            "class" => {
+                let class = string(p, "propertytype")?.unwrap_or("").to_string();
+                props.classes.insert(name, ClassProperty { class, props: class_members(value) });
+            },
+            t => { return Err(TiledError::new(ErrorKind::UnsupportedType(t.to_string()))); },
+        }
+    }
+    Ok(())
+}
+
+/// json class values don't say the type of each member, so it is taken from the json value
+fn class_members(value: &Value) -> Properties {
+    let mut props = Properties::blank();
+    let members = match value.as_object() {
+        Some(m) => m,
+        None => { return props; },
+    };
+    for (name, v) in members.iter() {
+        let name = name.clone();
+        match v {
+            Value::Bool(b) => { props.booleans.insert(name, *b); },
+            Value::Number(n) => match n.as_i64() {
+                Some(i) => { props.integers.insert(name, i); },
+                None => { props.floats.insert(name, n.as_f64().unwrap_or(0.0)); },
+            },
+            Value::String(s) => { props.strings.insert(name, s.clone()); },
+            Value::Object(_) => {
+                props.classes.insert(name, ClassProperty { class: String::new(), props: class_members(v) });
+            },
+            _ => error::warn(format!("unrecognized class member {:?}", name)),
+        }
+    }
+    props
+}
+
+fn parse_map_tileset(o: &Object, path: &str) -> Result<Tileset, TiledError> {
+    let mut ts = Tileset::blank();
+    ts.path = path.to_string();
+    ts.first_tile_id = int(o, "firstgid", 0)? as u32;
+    match string(o, "source")? {
+        Some(source) => ts.load_source(source)?,
+        None => parse_tileset(&mut ts, o)?,
+    }
+    Ok(ts)
+}
+
+/// read a .tsj file into `ts`, whose path should already be set to the file's directory
+pub fn parse_tileset_file(ts: &mut Tileset, text: &str) -> Result<(), TiledError> {
+    let v = parse(text)?;
+    parse_tileset(ts, as_object(&v, "tileset")?)
+}
+
+fn parse_tileset(ts: &mut Tileset, o: &Object) -> Result<(), TiledError> {
+    ts.name = string(o, "name")?.unwrap_or("").to_string();
+    ts.tile_width = int(o, "tilewidth", 0)? as u32;
+    ts.tile_height = int(o, "tileheight", 0)? as u32;
+    ts.spacing = int(o, "spacing", 0)? as u32;
+    ts.margin = int(o, "margin", 0)? as u32;
+    ts.tile_count = int(o, "tilecount", 0)? as u32;
+    ts.column_count = int(o, "columns", 0)? as u32;
+    ts.version = version(o, "version");
+    ts.tiledversion = version(o, "tiledversion");
+    if let Some(image) = string(o, "image")? {
+        ts.image_path = join_path(&ts.path, image);
+    }
+    ts.image_width = int(o, "imagewidth", 0)? as u32;
+    ts.image_height = int(o, "imageheight", 0)? as u32;
+    for t in list(o, "tiles")? {
+        let t = as_object(t, "tile")?;
+        let tile = parse_tile(t, &ts.path).map_err(in_object("tile", t))?;
+        ts.tiles.insert(tile.id, tile);
+    }
+    for t in list(o, "terrains")? {
+        let t = as_object(t, "terrain")?;
+        let mut terrain = Terrain::blank();
+        terrain.name = string(t, "name")?.unwrap_or("").to_string();
+        terrain.tile = tile_index(int(t, "tile", -1)?);
+        parse_properties(&mut terrain.props, t)?;
+        ts.terrains.push(terrain);
+    }
+    for w in list(o, "wangsets")? {
+        let w = as_object(w, "wangset")?;
+        ts.wang_sets.push(parse_wang_set(w).map_err(in_object("wangset", w))?);
+    }
+    Ok(())
+}
+
+fn parse_wang_set(o: &Object) -> Result<WangSet, TiledError> {
+    let mut set = WangSet::blank();
+    set.name = string(o, "name")?.unwrap_or("").to_string();
+    if let Some(set_type) = string(o, "type")? {
+        set.set_type = WangSetType::new(set_type)?;
+    }
+    set.tile = tile_index(int(o, "tile", -1)?);
+    parse_properties(&mut set.props, o)?;
+    for c in list(o, "colors")? {
+        let c = as_object(c, "wangcolor")?;
+        let mut colour = WangColour::blank();
+        colour.name = string(c, "name")?.unwrap_or("").to_string();
+        if let Some(hex) = string(c, "color")? {
+            colour.colour = colour_from_str(hex)?;
+        }
+        colour.tile = tile_index(int(c, "tile", -1)?);
+        colour.probability = num(c, "probability", 1.0)?;
+        parse_properties(&mut colour.props, c)?;
+        set.colours.push(colour);
+    }
+    for t in list(o, "wangtiles")? {
+        let t = as_object(t, "wangtile")?;
+        let mut wang_id = WangId([0; 8]);
+        let colours = list(t, "wangid")?;
+        if colours.len() != 8 {
+            return Err(TiledError::parse(String::from("wang id doesn't have 8 colours")));
+        }
+        for (colour, v) in wang_id.0.iter_mut().zip(colours) {
+            *colour = v.as_u64().ok_or_else(|| wrong_type("wangid"))? as u8;
+        }
+        set.tiles.insert(int(t, "tileid", 0)? as u32, wang_id);
+    }
+    Ok(set)
+}
+
+fn parse_tile(o: &Object, path: &str) -> Result<Tile, TiledError> {
+    let mut tile = Tile::blank(path);
+    tile.id = int(o, "id", 0)? as u32;
+    if let Some(type_name) = string(o, "type")?.or(string(o, "class")?) {
+        tile.type_name = type_name.to_string();
+    }
+    parse_properties(&mut tile.props, o)?;
+    if let Some(og) = o.get("objectgroup") {
+        tile.set_collision(parse_obj_group(as_object(og, "objectgroup")?, path, &mut Templates::new())?)?;
+    }
+    for f in list(o, "animation")? {
+        let f = as_object(f, "frame")?;
+        tile.animation.push(Frame {
+            tile_id: int(f, "tileid", 0)? as u32,
+            duration: int(f, "duration", 0)? as u32,
+        });
+    }
+    if let Some(image) = string(o, "image")? {
+        tile.image_path = join_path(path, image);
+    }
+    tile.image_width = int(o, "imagewidth", 0)? as u32;
+    tile.image_height = int(o, "imageheight", 0)? as u32;
+    let terrain = list(o, "terrain")?;
+    if !terrain.is_empty() {
+        if terrain.len() != 4 {
+            return Err(TiledError::parse(String::from("tile terrain doesn't have 4 corners")));
+        }
+        for (corner, v) in tile.terrain.iter_mut().zip(terrain) {
+            *corner = tile_index(v.as_i64().ok_or_else(|| wrong_type("terrain"))?);
+        }
+    }
+    tile.probability = num(o, "probability", 1.0)?;
+    Ok(tile)
+}
@@ -1,15 +1,22 @@
-use super::LayerTiles;
-use super::{Layer, Properties, error::TiledError, helper::*, LayerData};
+use super::{LayerTiles, Chunk, Gid};
+use super::{FLIPPED_HORIZONTALLY, FLIPPED_VERTICALLY, FLIPPED_DIAGONALLY, ROTATED_HEXAGONAL_120};
+use super::{Layer, Properties, error::{TiledError, ErrorKind}, helper::*, LayerData};
 
 use quick_xml::reader::Reader;
 use quick_xml::events::{BytesStart, BytesText};
 use quick_xml::events::attributes::Attribute;
 
+use std::io::Read;
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use flate2::read::{ZlibDecoder, GzDecoder};
+use ruzstd::decoding::StreamingDecoder;
+
 impl Layer {
-    fn blank() -> Layer {
+    pub(crate) fn blank() -> Layer {
         Layer {
             props: Properties::blank(),
             tiles: Vec::new(),
+            chunks: Vec::new(),
             width: 0,
             height: 0,
             info: LayerData::new(),
@@ -21,13 +28,45 @@ impl Layer {
         parse_xml(&mut layer, reader)?;
         Ok(layer)
     }
+    /// the raw tile id at tile coordinates `x`, `y`, 0 if there is no tile there
+    pub fn tile(&self, x: i32, y: i32) -> u32 {
+        if self.chunks.is_empty() {
+            return tile_in(&self.tiles, x, y, self.width, self.height);
+        }
+        for c in self.chunks.iter() {
+            if x >= c.x && x < c.x + c.width && y >= c.y && y < c.y + c.height {
+                return tile_in(&c.tiles, x - c.x, y - c.y, c.width, c.height);
+            }
+        }
+        0
+    }
+
+    /// the decoded tile at tile coordinates `x`, `y`
+    pub fn gid(&self, x: i32, y: i32) -> Gid {
+        Gid::new(self.tile(x, y))
+    }
+
+    /// the tile coordinates and raw tile id of every tile in the layer, skipping empty ones
+    pub fn cells(&self) -> Vec<(i32, i32, u32)> {
+        let mut cells = Vec::new();
+        if self.chunks.is_empty() {
+            add_cells(&mut cells, &self.tiles, 0, 0, self.width);
+        }
+        for c in self.chunks.iter() {
+            add_cells(&mut cells, &c.tiles, c.x, c.y, c.width);
+        }
+        cells
+    }
+
     fn parse_attribs(&mut self, attribs : Vec<Attribute>) -> Result<(), TiledError> {
         for a in attribs {
             if let Some(()) = self.info.handle_attrib(&a)? {
                 match a.key.as_ref() {
                     b"width" => self.width = get_value(&a.value)?,
                     b"height" => self.height = get_value(&a.value)?,
-                    _ => println!("warning: unrecognized atrribute {:?}", a.key),
+                    // where an infinite map's layer starts, the chunks have their own positions
+                    b"startx" | b"starty" => (),
+                    _ => unknown_attrib("layer", &a),
                 }
             }
         }
@@ -38,9 +77,12 @@ impl Layer {
 impl HandleXml for Layer {
     fn start(&mut self, e : &BytesStart, reader: &mut Reader<&[u8]>) -> Result<(), TiledError> {
         match e.name().as_ref() {
-            b"data" => parse_xml(&mut self.tiles, reader)?,
+            b"data" => parse_xml(
+                &mut TileData::new(collect_attribs(e)?, &mut self.tiles, Some(&mut self.chunks))?,
+                reader
+            )?,
             b"properties" => parse_xml(&mut self.props, reader)?,
-            _ => println!("unrecognized tag {:?}", e.name()),
+            _ => unknown_tag(Self::self_tag(), e),
         }
         Ok(())
     }
@@ -50,17 +92,147 @@ impl HandleXml for Layer {
     
  }
 
-impl HandleXml for LayerTiles {
-    fn text(&mut self, e : &BytesText) -> Result<(), TiledError> {
-        let data = match e.unescape() {
-            Ok(s) => s,
-            Err(_) => { return Err(TiledError::ParseError(String::from("tile data in layer could not be retrieved"))); },
+fn add_cells(cells: &mut Vec<(i32, i32, u32)>, tiles: &[u32], start_x: i32, start_y: i32, width: i32) {
+    if width <= 0 { return; }
+    for (i, tile) in tiles.iter().enumerate() {
+        if *tile != 0 {
+            cells.push((start_x + i as i32 % width, start_y + i as i32 / width, *tile));
+        }
+    }
+}
+
+fn tile_in(tiles: &LayerTiles, x: i32, y: i32, width: i32, height: i32) -> u32 {
+    if x < 0 || y < 0 || x >= width || y >= height {
+        return 0;
+    }
+    match tiles.get((y * width + x) as usize) {
+        Some(t) => *t,
+        None => 0,
+    }
+}
+
+impl Gid {
+    pub fn new(raw: u32) -> Gid {
+        Gid {
+            id: raw & !(FLIPPED_HORIZONTALLY | FLIPPED_VERTICALLY | FLIPPED_DIAGONALLY | ROTATED_HEXAGONAL_120),
+            flip_h: raw & FLIPPED_HORIZONTALLY != 0,
+            flip_v: raw & FLIPPED_VERTICALLY != 0,
+            flip_d: raw & FLIPPED_DIAGONALLY != 0,
+            rotate_hex_120: raw & ROTATED_HEXAGONAL_120 != 0,
+        }
+    }
+}
+
+impl Chunk {
+    fn new(attribs : Vec<Attribute>) -> Result<Chunk, TiledError> {
+        let mut chunk = Chunk { x: 0, y: 0, width: 0, height: 0, tiles: Vec::new() };
+        for a in attribs {
+            match a.key.as_ref() {
+                b"x" => chunk.x = get_value(&a.value)?,
+                b"y" => chunk.y = get_value(&a.value)?,
+                b"width" => chunk.width = get_value(&a.value)?,
+                b"height" => chunk.height = get_value(&a.value)?,
+                _ => unknown_attrib("chunk", &a),
+            }
+        }
+        Ok(chunk)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Encoding {
+    Xml,
+    Csv,
+    Base64,
+}
+
+#[derive(Clone, Copy)]
+enum Compression {
+    None,
+    Zlib,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    fn new(name: &str) -> Result<Compression, TiledError> {
+        match name {
+            "zlib" => Ok(Compression::Zlib),
+            "gzip" => Ok(Compression::Gzip),
+            "zstd" => Ok(Compression::Zstd),
+            "" => Ok(Compression::None),
+            _ => Err(TiledError::new(ErrorKind::UnsupportedType(name.to_string()))),
+        }
+    }
+
+    fn decompress(self, bytes: Vec<u8>) -> Result<Vec<u8>, TiledError> {
+        let mut out = Vec::new();
+        let result = match self {
+            Compression::None => { return Ok(bytes); },
+            Compression::Zlib => ZlibDecoder::new(bytes.as_slice()).read_to_end(&mut out),
+            Compression::Gzip => GzDecoder::new(bytes.as_slice()).read_to_end(&mut out),
+            Compression::Zstd => match StreamingDecoder::new(bytes.as_slice()) {
+                Ok(mut d) => d.read_to_end(&mut out),
+                Err(e) => { return Err(TiledError::parse(format!("tile data was not valid zstd: {}", e))); },
+            },
         };
-        for num in data.split(",") {
-            self.push( match num.trim().parse() {
+        match result {
+            Ok(_) => Ok(out),
+            Err(e) => Err(TiledError::parse(format!("tile data could not be decompressed: {}", e))),
+        }
+    }
+}
+
+/// decode base64 tile data, `compression` is named as in the map file, empty for none
+pub(crate) fn decode_base64(data: &str, compression: &str) -> Result<LayerTiles, TiledError> {
+    base64_tiles(data, Compression::new(compression)?)
+}
+
+fn base64_tiles(data: &str, compression: Compression) -> Result<LayerTiles, TiledError> {
+    let bytes = match BASE64.decode(data.trim()) {
+        Ok(b) => b,
+        Err(e) => { return Err(TiledError::parse(format!("tile data was not valid base64: {}", e))); },
+    };
+    let bytes = compression.decompress(bytes)?;
+    if bytes.len() % 4 != 0 {
+        return Err(TiledError::parse(String::from("decoded tile data was not a whole number of tiles")));
+    }
+    Ok(bytes.chunks_exact(4)
+       .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+       .collect())
+}
+
+/// reads the tile ids out of a `<data>` element in any of the encodings tiled can save
+pub struct TileData<'a> {
+    tiles: &'a mut LayerTiles,
+    chunks: Option<&'a mut Vec<Chunk>>,
+    encoding: Encoding,
+    compression: Compression,
+}
+
+impl<'a> TileData<'a> {
+    pub fn new(attribs : Vec<Attribute>, tiles: &'a mut LayerTiles, chunks: Option<&'a mut Vec<Chunk>>) -> Result<TileData<'a>, TiledError> {
+        let mut data = TileData { tiles, chunks, encoding: Encoding::Xml, compression: Compression::None };
+        for a in attribs {
+            match a.key.as_ref() {
+                b"encoding" => data.encoding = match get_string(&a.value)? {
+                    "csv" => Encoding::Csv,
+                    "base64" => Encoding::Base64,
+                    t => { return Err(TiledError::new(ErrorKind::UnsupportedType(t.to_string()))); },
+                },
+                b"compression" => data.compression = Compression::new(get_string(&a.value)?)?,
+                _ => unknown_attrib("data", &a),
+            }
+        }
+        Ok(data)
+    }
+
+    fn parse_csv(&mut self, data: &str) -> Result<(), TiledError> {
+        for num in data.split(',') {
+            self.tiles.push( match num.trim().parse() {
                 Ok(n) => n,
                 Err(_) => {
-                    return Err(TiledError::ParseError(
+                    return Err(TiledError::parse(
                         String::from("tile data could not be parsed to an integer: ") + num)
                     );
                 },
@@ -68,8 +240,85 @@ impl HandleXml for LayerTiles {
         }
         Ok(())
     }
-    
+
+    fn parse_base64(&mut self, data: &str) -> Result<(), TiledError> {
+        self.tiles.extend(base64_tiles(data, self.compression)?);
+        Ok(())
+    }
+}
+
+impl<'a> HandleXml for TileData<'a> {
+    fn start(&mut self, e : &BytesStart, reader: &mut Reader<&[u8]>) -> Result<(), TiledError> {
+        match e.name().as_ref() {
+            b"chunk" => {
+                let mut chunk = Chunk::new(collect_attribs(e)?)?;
+                parse_xml(
+                    &mut ChunkData(TileData {
+                        tiles: &mut chunk.tiles,
+                        chunks: None,
+                        encoding: self.encoding,
+                        compression: self.compression,
+                    }),
+                    reader
+                )?;
+                match self.chunks.as_mut() {
+                    Some(chunks) => chunks.push(chunk),
+                    None => { return Err(TiledError::parse(String::from("chunks cannot be nested"))); },
+                }
+            },
+            _ => unknown_tag(Self::self_tag(), e),
+        }
+        Ok(())
+    }
+
+    fn text(&mut self, e : &BytesText) -> Result<(), TiledError> {
+        let data = match e.unescape() {
+            Ok(s) => s,
+            Err(_) => { return Err(TiledError::parse(String::from("tile data in layer could not be retrieved"))); },
+        };
+        if data.trim().is_empty() {
+            return Ok(());
+        }
+        match self.encoding {
+            Encoding::Csv => self.parse_csv(&data),
+            Encoding::Base64 => self.parse_base64(&data),
+            Encoding::Xml => Err(TiledError::parse(String::from("xml tile data should only contain tile elements"))),
+        }
+    }
+
+    fn empty(&mut self, e : &BytesStart) -> Result<(), TiledError> {
+        match e.name().as_ref() {
+            b"tile" => {
+                let mut gid = 0;
+                for a in collect_attribs(e)? {
+                    match a.key.as_ref() {
+                        b"gid" => gid = get_value(&a.value)?,
+                        _ => unknown_attrib("tile", &a),
+                    }
+                }
+                self.tiles.push(gid);
+            },
+            _ => unknown_tag(Self::self_tag(), e),
+        }
+        Ok(())
+    }
+
     fn self_tag() -> &'static str {
         "data"
     }
 }
+
+/// the tiles of one `<chunk>`, encoded the same way as the `<data>` it is in
+struct ChunkData<'a>(TileData<'a>);
+
+impl<'a> HandleXml for ChunkData<'a> {
+    fn text(&mut self, e : &BytesText) -> Result<(), TiledError> {
+        self.0.text(e)
+    }
+    fn empty(&mut self, e : &BytesStart) -> Result<(), TiledError> {
+        self.0.empty(e)
+    }
+    fn self_tag() -> &'static str {
+        "chunk"
+    }
+}
@@ -10,16 +10,42 @@ mod layer;
 mod object_group;
 mod properties;
 mod image_layer;
+mod group;
+mod template;
+mod orientation;
+mod wang;
+pub use wang::Corners;
 mod helper;
+pub use helper::join_path;
+mod json;
+mod writer;
 use helper::*;
 pub mod error;
-use error::TiledError;
+use error::{TiledError, ErrorKind, Warning};
 
+#[derive(Clone)]
 pub struct Properties {
     pub booleans : HashMap<String, bool>,
     pub integers : HashMap<String, i64>,
+    pub floats : HashMap<String, f64>,
+    pub strings : HashMap<String, String>,
+    pub colours : HashMap<String, Colour>,
+    /// file paths are kept as written in the map
+    pub files : HashMap<String, String>,
+    /// the id of the referenced object, 0 when no object is set
+    pub objects : HashMap<String, u32>,
+    pub classes : HashMap<String, ClassProperty>,
 }
 
+/// a property of a custom class type, holding the members set on it
+#[derive(Clone)]
+pub struct ClassProperty {
+    /// the name of the custom type
+    pub class : String,
+    pub props : Properties,
+}
+
+#[derive(Clone)]
 pub struct LayerData {
     pub id: u32,
     pub name: String,
@@ -33,16 +59,74 @@ pub struct LayerData {
     pub offset: Vec2,
 } 
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LayerKind {
+    Tile,
+    Objects,
+    Image,
+}
+
+/// an entry in the map's layer tree, layers are an index into
+/// the map's list for their kind: `layers`, `obj_groups` or `img_layers`
+pub enum LayerNode {
+    Layer(LayerKind, usize),
+    Group(Box<Group>),
+}
+
+/// a group layer, its settings apply on top of the layers inside it
+pub struct Group {
+    pub info: LayerData,
+    pub props: Properties,
+    pub layers: Vec<LayerNode>,
+}
+
+/// a layer in draw order, with the settings of any groups it is in applied to `info`
+pub struct DrawLayer {
+    pub kind: LayerKind,
+    pub index: usize,
+    pub info: LayerData,
+}
+
+/// raw tile ids, flip flags included, use `Gid::new` to split them out
 pub type LayerTiles = Vec<u32>;
 
+const FLIPPED_HORIZONTALLY : u32 = 0x80000000;
+const FLIPPED_VERTICALLY : u32 = 0x40000000;
+const FLIPPED_DIAGONALLY : u32 = 0x20000000;
+const ROTATED_HEXAGONAL_120 : u32 = 0x10000000;
+
+/// a tile id with the flip flags tiled stores in its top bits decoded
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Gid {
+    pub id: u32,
+    pub flip_h: bool,
+    pub flip_v: bool,
+    /// swap the x and y axes, applied before the horizontal and vertical flips
+    pub flip_d: bool,
+    /// only used by hexagonal maps
+    pub rotate_hex_120: bool,
+}
+
+/// a piece of a layer in an infinite map, positioned in tiles
+pub struct Chunk {
+    pub x : i32,
+    pub y : i32,
+    pub width : i32,
+    pub height : i32,
+    pub tiles : LayerTiles,
+}
+
 pub struct Layer {
     pub props : Properties,
+    /// empty for infinite maps, which store their tiles in `chunks`
     pub tiles : LayerTiles,
+    pub chunks : Vec<Chunk>,
     pub width : i32,
     pub height: i32,
     pub info: LayerData,
 }
 
+#[derive(Clone)]
 pub struct ObjData {
     pub id: u32,
     pub name: String,
@@ -50,18 +134,26 @@ pub struct ObjData {
     pub visible: bool,
 }
 
+#[derive(Clone)]
 pub struct Obj {
     pub props : Properties,
     pub rect : Rect,
     pub rotation: f64,
     pub info: ObjData,
+    /// raw tile id with flip flags for tile objects, 0 for other objects
+    pub gid : u32,
     poly : Option<Box<Poly>>,
     text : Option<Box<Text>>,
     point: bool,
     ellipse: bool,
+    /// the template file, as written in the map
     pub template: Option<String>,
+    /// the first gid and file of the tileset a template's `gid` counts from,
+    /// until the map moves it into its own gids
+    template_tileset: Option<(u32, String)>,
 }
 
+#[derive(Clone)]
 pub struct Poly {
     pub points : Vec<Vec2>,
     pub obj : Obj,
@@ -71,7 +163,7 @@ pub struct Poly {
 type Point = Obj;
 type Ellipse = Obj;
 
-#[derive(Eq, PartialEq)]
+#[derive(Clone, Copy, Eq, PartialEq)]
 pub enum TextHorizontalAlign {
     Left,
     Center,
@@ -79,13 +171,14 @@ pub enum TextHorizontalAlign {
     Justify
 }
 
-#[derive(Eq, PartialEq)]
+#[derive(Clone, Copy, Eq, PartialEq)]
 pub enum TextVerticalAlign {
     Top,
     Center,
     Bottom
 }
 
+#[derive(Clone)]
 pub struct Text {
     pub obj: Obj,
     pub text: String,
@@ -120,6 +213,7 @@ pub struct ImageLayer {
     pub props: Properties,
 }
 
+#[derive(Clone)]
 pub struct Colour {
     pub r : u32,
     pub g : u32,
@@ -138,14 +232,94 @@ pub struct Tileset {
     pub margin : u32,
     pub spacing : u32,
 
+    /// empty for image collection tilesets, where each tile has its own image
     pub image_path : String,
     pub image_width : u32,
     pub image_height : u32,
 
     pub version : String,
     pub tiledversion : String,
+
+    /// tiles with extra data, keyed by their id within the tileset
+    pub tiles : HashMap<u32, Tile>,
+    /// terrains from before tiled 1.5, which replaced them with wang sets
+    pub terrains : Vec<Terrain>,
+    pub wang_sets : Vec<WangSet>,
+    /// the tileset file as the map refers to it, `None` when embedded in the map
+    pub source : Option<String>,
+    path : String,
+}
+
+/// a `tile` element from a tileset
+pub struct Tile {
+    pub id : u32,
+    pub type_name : String,
+    pub props : Properties,
+    /// collision shapes positioned relative to the top left of the tile
+    pub collision : Option<ObjGroup>,
+    /// empty unless the tile is animated
+    pub animation : Vec<Frame>,
+    /// only set for tiles in an image collection tileset
+    pub image_path : String,
+    pub image_width : u32,
+    pub image_height : u32,
+    /// index into the tileset's `terrains` at each corner: top left, top right, bottom left, bottom right
+    pub terrain : [Option<u32>; 4],
+    /// how likely the editor is to pick this tile over others that fit as well
+    pub probability : f64,
+    path : String,
+}
+
+/// one frame of an animated tile
+pub struct Frame {
+    /// the id of the tile to show, within the same tileset
+    pub tile_id : u32,
+    /// in milliseconds
+    pub duration : u32,
+}
+
+/// a `terrain` element, the old way of marking which tiles join up
+pub struct Terrain {
+    pub name : String,
+    /// the tile that shows the terrain in the editor, within the tileset
+    pub tile : Option<u32>,
+    pub props : Properties,
+}
+
+pub enum WangSetType {
+    Corner,
+    Edge,
+    Mixed,
+}
+
+/// tiles labelled with the colours at their corners and edges, so they can be matched up when auto-tiling
+pub struct WangSet {
+    pub name : String,
+    pub set_type : WangSetType,
+    /// the tile that shows the set in the editor, within the tileset
+    pub tile : Option<u32>,
+    /// the colours a `WangId` counts from 1
+    pub colours : Vec<WangColour>,
+    /// keyed by tile id within the tileset
+    pub tiles : HashMap<u32, WangId>,
+    pub props : Properties,
+}
+
+/// one kind of terrain in a wang set
+pub struct WangColour {
+    pub name : String,
+    pub colour : Colour,
+    pub tile : Option<u32>,
+    /// how likely the editor is to pick this colour over others that fit as well
+    pub probability : f64,
+    pub props : Properties,
 }
 
+/// the colour of each edge and corner of a tile, clockwise from the top edge,
+/// each is an index into the wang set's `colours` plus one, 0 where there is no colour
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct WangId(pub [u8; 8]);
+
 pub enum Orientation {
     Orthogonal,
     Isometric,
@@ -160,6 +334,94 @@ pub enum RenderOrder {
     LeftUp,
 }
 
+impl Orientation {
+    fn new(name: &str) -> Result<Orientation, TiledError> {
+        match name {
+            "orthogonal" => Ok(Orientation::Orthogonal),
+            "isometric" => Ok(Orientation::Isometric),
+            "staggered" => Ok(Orientation::IsometricStaggered),
+            "hexagonal" => Ok(Orientation::HexagonalStaggered),
+            _ => Err(TiledError::parse(String::from("unrecognized map orientation"))),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Orientation::Orthogonal => "orthogonal",
+            Orientation::Isometric => "isometric",
+            Orientation::IsometricStaggered => "staggered",
+            Orientation::HexagonalStaggered => "hexagonal",
+        }
+    }
+}
+
+impl RenderOrder {
+    fn new(name: &str) -> Result<RenderOrder, TiledError> {
+        match name {
+            "right-down" => Ok(RenderOrder::RightDown),
+            "right-up" => Ok(RenderOrder::RightUp),
+            "left-down" => Ok(RenderOrder::LeftDown),
+            "left-up" => Ok(RenderOrder::LeftUp),
+            _ => Err(TiledError::new(ErrorKind::UnsupportedType(name.to_string()))),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            RenderOrder::RightDown => "right-down",
+            RenderOrder::RightUp => "right-up",
+            RenderOrder::LeftDown => "left-down",
+            RenderOrder::LeftUp => "left-up",
+        }
+    }
+}
+
+/// which axis of a staggered or hexagonal map has every other row or column shifted
+pub enum StaggerAxis {
+    X,
+    Y,
+}
+
+/// whether the odd or even rows or columns are the shifted ones
+pub enum StaggerIndex {
+    Odd,
+    Even,
+}
+
+impl StaggerAxis {
+    fn new(name: &str) -> Result<StaggerAxis, TiledError> {
+        match name {
+            "x" => Ok(StaggerAxis::X),
+            "y" => Ok(StaggerAxis::Y),
+            _ => Err(TiledError::parse(format!("unrecognized stagger axis {:?}", name))),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            StaggerAxis::X => "x",
+            StaggerAxis::Y => "y",
+        }
+    }
+}
+
+impl StaggerIndex {
+    fn new(name: &str) -> Result<StaggerIndex, TiledError> {
+        match name {
+            "odd" => Ok(StaggerIndex::Odd),
+            "even" => Ok(StaggerIndex::Even),
+            _ => Err(TiledError::parse(format!("unrecognized stagger index {:?}", name))),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            StaggerIndex::Odd => "odd",
+            StaggerIndex::Even => "even",
+        }
+    }
+}
+
 pub struct MapMetadata {
     pub version : String,
     pub tiled_version : String,
@@ -174,20 +436,34 @@ pub struct Map {
     pub height : u32,
     pub tile_width : u32,
     pub tile_height : u32,
+    /// one more than the largest tile id used by the tilesets
     pub total_tiles : u32,
     pub infinite : bool,
     pub orientation : Orientation,
+    /// only used by staggered and hexagonal maps
+    pub stagger_axis : StaggerAxis,
+    pub stagger_index : StaggerIndex,
+    /// the length of the flat sides of a hexagonal tile
+    pub hex_side_length : u32,
 
     pub tilesets : Vec<Tileset>,
     pub layers : Vec<Layer>,
     pub obj_groups : Vec<ObjGroup>,
     pub img_layers : Vec<ImageLayer>,
     pub texts : Vec<Text>,
+    /// every layer and group in the order tiled draws them, bottom first
+    pub layer_tree : Vec<LayerNode>,
 
     pub path : String,
     pub metadata : MapMetadata,
+    /// things in the map or the files it uses that were skipped while reading it
+    pub warnings : Vec<Warning>,
+    templates : Templates,
 }
 
+/// template objects keyed by file, so each template is read once per map
+pub(crate) type Templates = HashMap<String, Obj>;
+
 impl Map {
     pub fn new(filename : &str) -> Result<Map, TiledError> {
         let path = match filename.rsplit_once('/') {
@@ -196,10 +472,16 @@ impl Map {
         };
         let mut path = path.to_owned();
         path.push('/');
-        Self::parse_xml(
-            read_file_to_string(filename)?,
-            &path
-        )
+        let warnings_start = error::warning_count();
+        let mut map = parse_file(filename, |text| {
+            if is_json(filename) {
+                json::parse_map(text, &path)
+            } else {
+                Self::parse_xml(text, &path)
+            }
+        })?;
+        map.warnings = error::take_warnings_from(warnings_start);
+        Ok(map)
     }
 
     fn blank_map(path: String) -> Map {
@@ -212,13 +494,19 @@ impl Map {
             total_tiles : 1,
             infinite : false,
             orientation : Orientation::Orthogonal,
+            stagger_axis : StaggerAxis::Y,
+            stagger_index : StaggerIndex::Odd,
+            hex_side_length : 0,
 
             tilesets : Vec::new(),
             layers : Vec::new(),
             obj_groups : Vec::new(),
             img_layers : Vec::new(),
             texts : Vec::new(),
+            layer_tree : Vec::new(),
             path,
+            warnings : Vec::new(),
+            templates : Templates::new(),
             metadata : MapMetadata {
                 version: "".to_string(),
                 tiled_version: "".to_string(),
@@ -237,63 +525,160 @@ impl Map {
                 b"tilewidth" => self.tile_width = get_value(&a.value)?,
                 b"tileheight" => self.tile_height = get_value(&a.value)?,
                 b"infinite" => self.infinite = get_value::<u32>(&a.value)? == 1,
-                b"orientation" => self.orientation =  match a.value.as_ref() {
-                    b"orthogonal" => Orientation::Orthogonal,
-                    b"isometric" => Orientation::Isometric,
-                    b"staggard" => Orientation::IsometricStaggered,
-                    b"hexagonal" => Orientation::HexagonalStaggered,
-                    _ => panic!("unrecognized map orientation"),
-                },
+                b"orientation" => self.orientation = Orientation::new(get_string(&a.value)?)?,
+                b"staggeraxis" => self.stagger_axis = StaggerAxis::new(get_string(&a.value)?)?,
+                b"staggerindex" => self.stagger_index = StaggerIndex::new(get_string(&a.value)?)?,
+                b"hexsidelength" => self.hex_side_length = get_value(&a.value)?,
                 b"version" => self.metadata.version = get_string(&a.value)?.to_string(),
                 b"tiledversion" => self.metadata.tiled_version = get_string(&a.value)?.to_string(),
                 b"nextlayerid" => self.metadata.next_layer_id = get_value(&a.value)?,
                 b"nextobjectid" => self.metadata.next_object_id = get_value(&a.value)?,
-                b"renderorder" => self.metadata.render_order = match a.value.as_ref() {
-                    b"right-down" => RenderOrder::RightDown,
-                    b"right-up" => RenderOrder::RightUp,
-                    b"left-down" => RenderOrder::LeftDown,
-                    b"left-up" => RenderOrder::LeftUp,
-                    _ => { return Err(TiledError::UnsupportedType()); },
-                },
-                _ => println!("warning: unrecognized atrribute {:?}", a.key),
+                b"renderorder" => self.metadata.render_order = RenderOrder::new(get_string(&a.value)?)?,
+                _ => unknown_attrib("map", &a),
             }
         }
         Ok(())
     }
 
-    fn parse_xml(map_file_text : String, path : &str) -> Result<Map, TiledError> {
-        let mut reader = Reader::from_str(&map_file_text);
+    /// the tile id at a point in the map, 0 if there is no tile there
+    pub fn tile_at(&self, layer: &Layer, pos: Vec2) -> u32 {
+        let (x, y) = self.world_to_tile(pos);
+        layer.tile(x, y)
+    }
+
+    /// read a layer of any kind, adding it to the list for its kind
+    fn parse_layer(&mut self, e : &BytesStart, reader: &mut Reader<&[u8]>) -> Result<LayerNode, TiledError> {
+        Ok(match e.name().as_ref() {
+            b"layer" => {
+                self.layers.push(Layer::new(collect_attribs(e)?, reader)?);
+                LayerNode::Layer(LayerKind::Tile, self.layers.len() - 1)
+            },
+            b"objectgroup" => {
+                self.obj_groups.push(ObjGroup::new(collect_attribs(e)?, reader, self.path.clone(), &mut self.templates)?);
+                LayerNode::Layer(LayerKind::Objects, self.obj_groups.len() - 1)
+            },
+            b"imagelayer" => {
+                self.img_layers.push(ImageLayer::new(collect_attribs(e)?, reader)?);
+                LayerNode::Layer(LayerKind::Image, self.img_layers.len() - 1)
+            },
+            b"group" => LayerNode::Group(Box::new(Group::parse(collect_attribs(e)?, self, reader)?)),
+            _ => { return Err(TiledError::parse(format!("{:?} is not a layer", e.name()))); },
+        })
+    }
+
+    /// every layer in draw order, bottom first
+    pub fn draw_order(&self) -> Vec<DrawLayer> {
+        let mut order = Vec::new();
+        self.add_draw_layers(&mut order, &self.layer_tree, None);
+        order
+    }
+
+    fn add_draw_layers(&self, order: &mut Vec<DrawLayer>, nodes: &[LayerNode], group: Option<&LayerData>) {
+        for node in nodes {
+            match node {
+                LayerNode::Layer(kind, index) => {
+                    let info = match kind {
+                        LayerKind::Tile => &self.layers[*index].info,
+                        LayerKind::Objects => &self.obj_groups[*index].info,
+                        LayerKind::Image => &self.img_layers[*index].info,
+                    };
+                    order.push(DrawLayer {
+                        kind: *kind,
+                        index: *index,
+                        info: match group {
+                            Some(g) => info.in_group(g),
+                            None => info.clone(),
+                        },
+                    });
+                },
+                LayerNode::Group(g) => {
+                    let info = match group {
+                        Some(parent) => g.info.in_group(parent),
+                        None => g.info.clone(),
+                    };
+                    self.add_draw_layers(order, &g.layers, Some(&info));
+                },
+            }
+        }
+    }
+
+    fn add_tileset(&mut self, ts: Tileset) {
+        self.total_tiles = self.total_tiles.max(ts.first_tile_id + ts.id_count());
+        self.tilesets.push(ts);
+    }
+
+    /// the tileset a tile id is from and the tile's id within it, with any flip flags removed
+    pub fn tileset_for(&self, tile_id: u32) -> Option<(&Tileset, u32)> {
+        let id = Gid::new(tile_id).id;
+        let ts = self.tilesets.iter()
+            .filter(|ts| ts.first_tile_id <= id)
+            .max_by_key(|ts| ts.first_tile_id)?;
+        Some((ts, id - ts.first_tile_id))
+    }
+
+    /// the tileset data for a tile id, with any flip flags removed
+    pub fn tile_data(&self, tile_id: u32) -> Option<&Tile> {
+        let (ts, id) = self.tileset_for(tile_id)?;
+        ts.tiles.get(&id)
+    }
+
+    fn parse_xml(map_file_text : &str, path : &str) -> Result<Map, TiledError> {
+        let mut reader = Reader::from_str(map_file_text);
         let mut map = Self::blank_map(path.to_string());
         parse_xml(&mut map, &mut reader)?;
-        map.rect = Rect::new(
-            0.0, 0.0,
-            (map.width * map.tile_width) as f64,
-            (map.height * map.tile_height) as f64
-        );
+        map.resolve_template_tilesets()?;
+        map.set_rect();
         Ok(map)
     }
+
+    fn set_rect(&mut self) {
+        self.rect = self.tiles_bounds(0, 0, self.width as i32, self.height as i32);
+        if self.infinite {
+            self.rect = self.chunk_bounds();
+        }
+    }
+
+    /// the area covered by every chunk of every layer, in pixels
+    fn chunk_bounds(&self) -> Rect {
+        let chunks = self.layers.iter().flat_map(|l| l.chunks.iter());
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (i32::MAX, i32::MAX, i32::MIN, i32::MIN);
+        for c in chunks {
+            min_x = min_x.min(c.x);
+            min_y = min_y.min(c.y);
+            max_x = max_x.max(c.x + c.width);
+            max_y = max_y.max(c.y + c.height);
+        }
+        if min_x > max_x {
+            return Rect::blank();
+        }
+        self.tiles_bounds(min_x, min_y, max_x, max_y)
+    }
 }
 
 impl HandleXml for Map {
     fn start(&mut self, e : &BytesStart, reader: &mut Reader<&[u8]>) -> Result<(), TiledError> {
         match e.name().as_ref() {
             b"map" => self.parse_map_attribs(collect_attribs(&e)?)?,
-            b"layer" => self.layers.push(Layer::new(collect_attribs(&e)?, reader)?), //add layer properly
-            b"objectgroup" => self.obj_groups.push(ObjGroup::new(collect_attribs(&e)?, reader, self.path.clone())?),
-            b"imagelayer" => self.img_layers.push(ImageLayer::new(collect_attribs(&e)?, reader)?),
-            _ => println!("unrecognized tag {:?}", e.name()),
+            b"layer" | b"objectgroup" | b"imagelayer" | b"group" => {
+                let node = self.parse_layer(e, reader)?;
+                self.layer_tree.push(node);
+            },
+            b"tileset" => {
+                let ts = Tileset::new(collect_attribs(e)?, self.path.clone(), Some(reader))?;
+                self.add_tileset(ts);
+            },
+            _ => unknown_tag("map", e),
         }
         Ok(())
     }
     fn empty(&mut self, e : &BytesStart) -> Result<(), TiledError> {
         match e.name().as_ref() {
             b"tileset" => {
-                self.tilesets.push(
-                    Tileset::new(collect_attribs(&e)?, self.path.clone())?
-                );
-                self.total_tiles += self.tilesets.last().unwrap().tile_count;
+                let ts = Tileset::new(collect_attribs(e)?, self.path.clone(), None)?;
+                self.add_tileset(ts);
             },
-            _ => println!("unrecognized empty tag {:?}", e.name()),
+            b"group" => self.layer_tree.push(LayerNode::Group(Box::new(Group::new(collect_attribs(e)?)?))),
+            _ => unknown_tag("map", e),
         }
         Ok(())
     }
@@ -307,7 +692,16 @@ mod tiled_tests {
     use super::*;
     #[test]
     fn test_map() {
-        let map = Map::new("test-resources/test.tmx").unwrap();
+        check_test_map(&Map::new("test-resources/test.tmx").unwrap());
+    }
+
+    #[test]
+    fn test_json_map() {
+        check_test_map(&Map::new("test-resources/test.tmj").unwrap());
+    }
+
+    /// the xml and json versions of the test map should load the same
+    fn check_test_map(map: &Map) {
         assert!(map.width == 4);
         assert!(map.height == 4);
         assert!(map.tile_width == 10);
@@ -461,5 +855,450 @@ mod tiled_tests {
         assert!(map.img_layers[0].info.parallax.x == 2.07);
         assert!(map.img_layers[0].info.parallax.y ==  1.0);
         assert!(map.img_layers[0].props.booleans["img"] == false);
+
+        assert!(
+            map.draw_order().iter().map(|l| (l.kind, l.index)).collect::<Vec<_>>() == vec![
+                (LayerKind::Tile, 0),
+                (LayerKind::Tile, 1),
+                (LayerKind::Objects, 0),
+                (LayerKind::Image, 0),
+                (LayerKind::Objects, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tile_encodings() {
+        let map = Map::new("test-resources/encoded.tmx").unwrap();
+        assert!(map.layers.len() == 5);
+        for l in map.layers.iter() {
+            assert!(
+                l.tiles == vec![
+                    4, 4, 0, 0,
+                    2, 2, 2, 0,
+                    2, 2, 2, 0,
+                    4, 4, 0, 0,
+                ],
+                "layer {} decoded wrong", l.info.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_infinite_map() {
+        let map = Map::new("test-resources/infinite.tmx").unwrap();
+        assert!(map.infinite);
+        let l = &map.layers[0];
+        assert!(l.tiles.is_empty());
+        assert!(l.chunks.len() == 2);
+        assert!(l.chunks[0].x == -4);
+        assert!(l.chunks[0].y == 0);
+        assert!(l.chunks[1].width == 4);
+        assert!(l.chunks[1].height == 4);
+        assert!(l.tile(-1, 0) == 1);
+        assert!(l.tile(-4, 0) == 0);
+        assert!(l.tile(-4, 3) == 1);
+        assert!(l.tile(0, -4) == 2);
+        assert!(l.tile(1, -3) == 2);
+        assert!(l.tile(1, -4) == 0);
+        assert!(l.tile(100, 100) == 0);
+        assert!(map.tile_at(l, Vec2::new(-5.0, 35.0)) == 1);
+        assert!(map.tile_at(l, Vec2::new(15.0, -25.0)) == 2);
+        assert!(map.rect.x == -40.0);
+        assert!(map.rect.y == -40.0);
+        assert!(map.rect.w == 80.0);
+        assert!(map.rect.h == 80.0);
+    }
+
+    #[test]
+    fn test_flipped_tiles() {
+        let map = Map::new("test-resources/flipped.tmx").unwrap();
+        let l = &map.layers[0];
+        let gid = |x, y| {
+            let g = l.gid(x, y);
+            (g.id, g.flip_h, g.flip_v, g.flip_d)
+        };
+        assert!(gid(0, 0) == (2, false, false, false));
+        assert!(gid(1, 0) == (2, true, false, false));
+        assert!(gid(2, 0) == (4, false, true, false));
+        assert!(gid(0, 1) == (1, false, false, true));
+        assert!(gid(1, 1) == (2, true, true, false));
+        assert!(gid(2, 1) == (1, true, false, true));
+    }
+
+    #[test]
+    fn test_property_types() {
+        let map = Map::new("test-resources/properties.tmx").unwrap();
+        let obj = map.obj_groups[0].objs.iter().find(|o| o.info.id == 1).unwrap();
+        let props = &obj.props;
+        assert!(props.strings["name"] == "door");
+        assert!(props.strings["label"] == "a & b");
+        assert!(props.strings["desc"] == "first line\nsecond line");
+        assert!(props.floats["speed"] == 2.5);
+        let tint = &props.colours["tint"];
+        assert!((tint.r, tint.g, tint.b, tint.a) == (255, 0, 16, 128));
+        assert!(props.colours["unset"].a == 0);
+        assert!(props.files["script"] == "scripts/door.txt");
+        assert!(props.objects["target"] == 2);
+        assert!(props.integers["count"] == -3);
+        assert!(!props.booleans["open"]);
+
+        let spawn = &props.classes["spawn"];
+        assert!(spawn.class == "Spawner");
+        assert!(spawn.props.floats["rate"] == 0.5);
+        assert!(spawn.props.classes["inner"].class == "Point");
+        assert!(spawn.props.classes["inner"].props.integers["x"] == 4);
+        assert!(props.classes["empty"].props.floats.is_empty());
+    }
+
+    #[test]
+    fn test_tile_collision() {
+        let map = Map::new("test-resources/collision.tmx").unwrap();
+        let ts = &map.tilesets[0];
+        assert!(ts.tiles.len() == 4);
+        assert!(ts.tiles[&1].type_name == "ground");
+        assert!(ts.tiles[&1].props.booleans["solid"]);
+        let coll = ts.tiles[&1].collision.as_ref().unwrap();
+        assert!(coll.objs.len() == 1);
+        let r = coll.objs[0].rect;
+        assert!((r.x, r.y, r.w, r.h) == (0.0, 5.0, 10.0, 5.0));
+        let coll = ts.tiles[&2].collision.as_ref().unwrap();
+        assert!(coll.objs.len() == 1);
+        assert!(coll.polys.len() == 1);
+        assert!(ts.tiles[&3].type_name == "decoration");
+        assert!(ts.tiles[&3].collision.is_none());
+
+        let l = &map.layers[0];
+        assert!(map.tile_data(l.tile(0, 1)).unwrap().id == 1);
+        assert!(map.tile_data(l.tile(2, 1)).unwrap().id == 1);
+        assert!(map.tile_data(l.tile(1, 1)).unwrap().id == 2);
+        assert!(map.tile_data(l.tile(0, 0)).is_none());
+    }
+
+    #[test]
+    fn test_tile_animation() {
+        let map = Map::new("test-resources/collision.tmx").unwrap();
+        let ts = &map.tilesets[0];
+        let frames = &ts.tiles[&0].animation;
+        assert!(frames.len() == 2);
+        assert!((frames[0].tile_id, frames[0].duration) == (0, 100));
+        assert!((frames[1].tile_id, frames[1].duration) == (3, 250));
+        assert!(ts.tiles[&1].animation.is_empty());
+    }
+
+    #[test]
+    fn test_embedded_tilesets() {
+        let map = Map::new("test-resources/embedded.tmx").unwrap();
+        assert!(map.tilesets.len() == 2);
+        let grid = &map.tilesets[0];
+        assert!(grid.name == "grid");
+        assert!(grid.first_tile_id == 1);
+        assert!(grid.tile_count == 4);
+        assert!(grid.column_count == 2);
+        assert!(grid.image_path == "test-resources/test-tileset.png");
+        assert!(grid.tiles[&2].type_name == "water");
+
+        let collection = &map.tilesets[1];
+        assert!(collection.first_tile_id == 5);
+        assert!(collection.image_path.is_empty());
+        assert!(collection.tiles.len() == 2);
+        assert!(collection.tiles[&0].image_path == "test-resources/test-tileset.png");
+        let small = &collection.tiles[&3];
+        assert!(small.image_path == "test-resources/sprites/small.png");
+        assert!((small.image_width, small.image_height) == (16, 8));
+        // ids in a collection can skip past the tile count
+        assert!(collection.id_count() == 4);
+        assert!(map.total_tiles == 9);
+        assert!(map.tile_data(map.layers[0].tile(1, 1)).unwrap().image_width == 16);
+    }
+
+    /// parse the tmx a map writes, as if it was saved over the original
+    fn round_trip(map: &Map) -> Map {
+        Map::parse_xml(&map.to_tmx().unwrap(), &map.path).unwrap()
+    }
+
+    #[test]
+    fn test_write_round_trip() {
+        let map = Map::new("test-resources/test.tmx").unwrap();
+        let written = round_trip(&map);
+        check_test_map(&written);
+        assert!(written.to_tmx().unwrap() == map.to_tmx().unwrap());
+
+        for file in ["encoded", "infinite", "flipped", "properties", "collision", "embedded", "group", "templates", "isometric", "hexagonal", "staggered", "wang"] {
+            let map = Map::new(&format!("test-resources/{}.tmx", file)).unwrap();
+            let tmx = map.to_tmx().unwrap();
+            assert!(round_trip(&map).to_tmx().unwrap() == tmx, "{} changed after writing", file);
+        }
+    }
+
+    #[test]
+    fn test_write_keeps_data() {
+        let map = round_trip(&Map::new("test-resources/properties.tmx").unwrap());
+        let obj = map.obj_groups[0].objs.iter().find(|o| o.info.id == 1).unwrap();
+        assert!(obj.props.strings["desc"] == "first line\nsecond line");
+        assert!(obj.props.strings["label"] == "a & b");
+        assert!(obj.props.colours["tint"].a == 128);
+        assert!(obj.props.colours["unset"].a == 0);
+        assert!(obj.props.classes["spawn"].props.classes["inner"].props.integers["x"] == 4);
+
+        let map = round_trip(&Map::new("test-resources/infinite.tmx").unwrap());
+        assert!(map.layers[0].chunks.len() == 2);
+        assert!(map.layers[0].tile(-4, 0) == Map::new("test-resources/infinite.tmx").unwrap().layers[0].tile(-4, 0));
+
+        let map = round_trip(&Map::new("test-resources/embedded.tmx").unwrap());
+        assert!(map.tilesets[0].source.is_none());
+        assert!(map.tilesets[0].image_path == "test-resources/test-tileset.png");
+        assert!(map.tilesets[1].tiles[&3].image_path == "test-resources/sprites/small.png");
+
+        let map = round_trip(&Map::new("test-resources/collision.tmx").unwrap());
+        assert!(map.tilesets[0].source.as_deref() == Some("collision.tsx"));
+    }
+
+    #[test]
+    fn test_group_layers() {
+        for file in ["test-resources/group.tmx", "test-resources/group.tmj"] {
+            let map = Map::new(file).unwrap();
+            check_group_map(&map);
+            check_group_map(&round_trip(&map));
+        }
+    }
+
+    fn check_group_map(map: &Map) {
+        assert!(map.layers.len() == 3);
+        assert!(map.obj_groups.len() == 1);
+        assert!(map.img_layers.len() == 1);
+        assert!(map.layer_tree.len() == 3);
+        let outer = match &map.layer_tree[1] {
+            LayerNode::Group(g) => g,
+            _ => panic!("expected a group"),
+        };
+        assert!(outer.info.name == "outer");
+        assert!(outer.props.integers["depth"] == 2);
+        assert!(outer.layers.len() == 3);
+
+        let order = map.draw_order();
+        assert!(
+            order.iter().map(|l| (l.kind, l.index)).collect::<Vec<_>>() == vec![
+                (LayerKind::Tile, 0),
+                (LayerKind::Image, 0),
+                (LayerKind::Tile, 1),
+                (LayerKind::Objects, 0),
+                (LayerKind::Tile, 2),
+            ]
+        );
+        assert!(order[0].info.offset.x == 0.0);
+        let sky = &order[1].info;
+        assert!(sky.opacity == 0.25);
+        assert!(sky.parallax.x == 0.25);
+        assert!((sky.offset.x, sky.offset.y) == (10.0, 5.0));
+        let detail = &order[2].info;
+        assert!(detail.offset.x == 13.0);
+        assert!((detail.tint.r, detail.tint.g, detail.tint.b) == (128, 64, 0));
+        assert!(order[3].info.opacity == 0.5);
+        assert!(order[4].info.opacity == 1.0);
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = match Map::new("test-resources/broken.tmx") {
+            Err(e) => e,
+            Ok(_) => panic!("broken map should not load"),
+        };
+        assert!(err.file.as_deref() == Some("test-resources/broken.tmx"));
+        assert!(err.element.as_deref() == Some("layer"));
+        assert!(err.line == Some((4, 2)));
+        assert!(err.to_string().starts_with("test-resources/broken.tmx:4:2: in layer: "));
+
+        let err = match Map::new("test-resources/missing.tmx") {
+            Err(e) => e,
+            Ok(_) => panic!("missing map should not load"),
+        };
+        assert!(matches!(err.kind, ErrorKind::FileReadError(_)));
+        assert!(err.file.as_deref() == Some("test-resources/missing.tmx"));
+
+        let err = match json::parse_map("{\n \"width\": }", "") {
+            Err(e) => e,
+            Ok(_) => panic!("invalid json should not load"),
+        };
+        assert!(err.line.map(|(line, _)| line) == Some(2));
+    }
+
+    #[test]
+    fn test_warnings() {
+        let map = Map::new("test-resources/warnings.tmx").unwrap();
+        assert!(map.warnings.len() == 2);
+        assert!(map.warnings.iter().all(|w| w.file.as_deref() == Some("test-resources/warnings.tmx")));
+        assert!(map.warnings[0].message.contains("shiny"));
+        assert!(map.warnings[1].message.contains("sparkles"));
+        assert!(error::take_warnings().is_empty());
+        assert!(Map::new("test-resources/test.tmx").unwrap().warnings.is_empty());
+    }
+
+    #[test]
+    fn test_templates() {
+        let map = Map::new("test-resources/templates.tmx").unwrap();
+        let obj = |id| map.obj_groups[0].objs.iter().find(|o| o.info.id == id).unwrap();
+
+        let plain = obj(1);
+        assert!(plain.template.as_deref() == Some("test.tx"));
+        assert!(plain.info.type_name == "asd");
+        assert!((plain.rect.x, plain.rect.y, plain.rect.w) == (1.0, 2.0, 15.1719));
+        assert!(plain.rotation == 343.734);
+        assert!(plain.props.integers["num"] == 5);
+        assert!(plain.props.booleans["test"]);
+
+        // the instance's attributes and properties win over the template's
+        let changed = obj(2);
+        assert!(changed.info.type_name == "override");
+        assert!((changed.rect.w, changed.rect.h) == (2.0, 18.3066));
+        assert!(changed.rotation == 0.0);
+        assert!(changed.props.integers["num"] == 7);
+        assert!(changed.props.booleans["test"]);
+
+        // the template's tileset is test.tsx at gid 1, which the map has at gid 5
+        let tile = obj(3);
+        assert!(tile.info.name == "crate");
+        assert!(tile.gid == 0x80000007);
+        assert!(tile.props.booleans["push"]);
+        assert!(obj(4).gid == 6);
+
+        // tile objects are placed by their bottom left corner
+        assert!(plain.tile().is_none());
+        let gid = tile.tile().unwrap();
+        assert!(gid.id == 7 && gid.flip_h && !gid.flip_v);
+        assert!((tile.bounds().x, tile.bounds().y, tile.bounds().h) == (5.0, -4.0, 10.0));
+        assert!(plain.bounds().y == plain.rect.y);
+        let (ts, id) = map.tileset_for(tile.gid).unwrap();
+        assert!(ts.source.as_deref() == Some("test.tsx") && id == 2);
+
+        // a template reached by a different path is still only read once
+        assert!(obj(5).info.type_name == "asd");
+        assert!(map.templates.len() == 2);
+    }
+
+    #[test]
+    fn test_templates_in_tile_collision() {
+        for source in ["templated_collision.tsx", "templated_collision.tsj"] {
+            let mut ts = Tileset::blank();
+            ts.path = String::from("test-resources/");
+            let err = match ts.load_source(source) {
+                Err(e) => e.to_string(),
+                Ok(()) => panic!("{} should not load", source),
+            };
+            assert!(err.contains("tile 1 collision uses template templates/tile.tx"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_tileset_paths_are_normalised() {
+        for source in ["../test-resources/test.tsx", "../test-resources/test.tsj"] {
+            let mut ts = Tileset::blank();
+            ts.path = String::from("test-resources/");
+            ts.load_source(source).unwrap();
+            assert!(ts.path == "test-resources/");
+            assert!(ts.image_path == "test-resources/test-tileset.png", "{}", ts.image_path);
+        }
+    }
+
+    #[test]
+    fn test_orientations() {
+        let xy = |v: Vec2| (v.x, v.y);
+        let iso = Map::new("test-resources/isometric.tmx").unwrap();
+        assert!(matches!(iso.orientation, Orientation::Isometric));
+        // the top corner of the map is the top of the tile at 0, 0
+        assert!(xy(iso.tile_to_world(0, 0)) == (16.0, 0.0));
+        assert!(xy(iso.tile_to_world(2, 0)) == (48.0, 16.0));
+        assert!(xy(iso.tile_to_world(0, 1)) == (0.0, 8.0));
+        assert!((iso.rect.x, iso.rect.y, iso.rect.w, iso.rect.h) == (0.0, 0.0, 80.0, 40.0));
+        assert!(iso.tile_at(&iso.layers[0], Vec2::new(48.0, 32.0)) == 1);
+        // the corner of the tile's box is in its neighbour
+        assert!(iso.world_to_tile(Vec2::new(17.0, 1.0)) == (-1, 0));
+        // objects are a tile height to a tile along each axis, starting from the same top corner
+        assert!(xy(iso.object_to_world(Vec2::new(0.0, 0.0))) == (32.0, 0.0));
+        assert!(xy(iso.object_to_world(Vec2::new(16.0, 0.0))) == (48.0, 8.0));
+        assert!(xy(iso.object_to_world(Vec2::new(8.0, 24.0))) == (16.0, 16.0));
+
+        for file in ["hexagonal.tmx", "hexagonal.tmj"] {
+            let hex = Map::new(&format!("test-resources/{}", file)).unwrap();
+            assert!(matches!(hex.orientation, Orientation::HexagonalStaggered));
+            assert!(matches!(hex.stagger_axis, StaggerAxis::Y));
+            assert!(matches!(hex.stagger_index, StaggerIndex::Odd));
+            assert!(hex.hex_side_length == 6);
+            assert!(xy(hex.tile_to_world(0, 1)) == (7.0, 9.0));
+            assert!(xy(hex.tile_to_world(2, 2)) == (28.0, 18.0));
+            assert!((hex.rect.x, hex.rect.y, hex.rect.w, hex.rect.h) == (0.0, 0.0, 49.0, 30.0));
+        }
+
+        let staggered = Map::new("test-resources/staggered.tmx").unwrap();
+        assert!(matches!(staggered.orientation, Orientation::IsometricStaggered));
+        assert!(matches!(staggered.stagger_axis, StaggerAxis::X));
+        assert!(matches!(staggered.stagger_index, StaggerIndex::Even));
+        assert!(xy(staggered.tile_to_world(0, 0)) == (0.0, 8.0));
+        assert!(xy(staggered.tile_to_world(1, 0)) == (16.0, 0.0));
+        assert!((staggered.rect.x, staggered.rect.y, staggered.rect.w, staggered.rect.h) == (0.0, 0.0, 64.0, 40.0));
+        // staggered maps keep objects where they are drawn
+        assert!(xy(staggered.object_to_world(Vec2::new(5.0, 7.0))) == (5.0, 7.0));
+
+        // the centre of every tile maps back to it
+        for file in ["test.tmx", "isometric.tmx", "hexagonal.tmx", "staggered.tmx"] {
+            let map = Map::new(&format!("test-resources/{}", file)).unwrap();
+            for y in -2..5 {
+                for x in -2..5 {
+                    let centre = map.tile_rect(x, y).centre();
+                    assert!(map.world_to_tile(centre) == (x, y), "{} {} {}", file, x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_wang_sets() {
+        let map = Map::new("test-resources/wang.tmx").unwrap();
+        let mut json = Tileset::blank();
+        json.path = String::from("test-resources/");
+        json.load_source("wang.tsj").unwrap();
+        assert!(map.warnings.is_empty());
+        for ts in [
+            &map.tilesets[0],
+            &map.tilesets[1],
+            &json,
+            &round_trip(&map).tilesets[0],
+        ] {
+            assert!(ts.terrains.len() == 2);
+            assert!(ts.terrains[0].name == "grass");
+            assert!(ts.terrains[0].tile == Some(0));
+            assert!(ts.terrains[1].tile.is_none());
+            assert!(ts.terrains[1].props.booleans["swim"]);
+            assert!(ts.tiles[&0].type_name == "ground");
+            assert!(ts.tiles[&1].terrain == [Some(0), Some(1), None, Some(1)]);
+            assert!(ts.tiles[&1].probability == 0.25);
+            assert!(ts.tiles[&0].probability == 1.0);
+
+            assert!(ts.wang_sets.len() == 2);
+            let ground = &ts.wang_sets[0];
+            assert!(matches!(ground.set_type, WangSetType::Corner));
+            assert!(ground.props.strings["layer"] == "floor");
+            assert!(ground.colours[1].name == "dirt");
+            assert!(ground.colours[1].probability == 0.5);
+            assert!(ground.colours[1].props.booleans["slow"]);
+            assert!((ground.colours[1].colour.r, ground.colours[1].colour.g) == (0x80, 0x40));
+            assert!(ground.tiles[&2].corners() == [1, 1, 2, 2]);
+            let paths = &ts.wang_sets[1];
+            assert!(matches!(paths.set_type, WangSetType::Edge));
+            assert!(paths.tile == Some(3));
+            assert!(paths.tiles[&0].edges() == [1, 1, 0, 0]);
+
+            // wang sets come first, then old style terrains
+            assert!(ts.corners(2) == Some([Some("grass"), Some("grass"), Some("dirt"), Some("dirt")]));
+            assert!(ts.corners(1) == Some([Some("grass"), Some("water"), None, Some("water")]));
+            assert!(ts.corners(0) == Some([Some("grass"); 4]));
+        }
+
+        // flipped horizontally, then vertically
+        let l = &map.layers[0];
+        assert!(map.tile_corners(l.tile(0, 0)) == Some([Some("water"), Some("grass"), Some("water"), None]));
+        assert!(map.tile_corners(l.tile(1, 0)) == Some([Some("dirt"), Some("dirt"), Some("grass"), Some("grass")]));
+        assert!(map.tile_corners(5 + 3) == Some([Some("dirt"); 4]));
+        assert!(map.tile_corners(0).is_none());
     }
 }
@@ -1,7 +1,7 @@
 
 use super::{helper::*, LayerData, ObjData, Text, TextHorizontalAlign, TextVerticalAlign, Colour};
-use super::error::TiledError;
-use super::{ObjGroup, Obj, Poly, Properties};
+use super::error::{TiledError, ErrorKind};
+use super::{ObjGroup, Obj, Poly, Properties, Templates, Gid};
 use geometry::{Rect, Vec2};
 
 use quick_xml::events::attributes::Attribute;
@@ -20,24 +20,40 @@ impl ObjData {
 }
 
 impl Obj {
-    fn blank() -> Obj {
-        Obj { props: Properties::blank(), rect: Rect::new(0.0, 0.0, 0.0, 0.0), info: ObjData::blank(), poly: None, text: None, rotation: 0.0, point: false, ellipse: false, template: None}
+    pub(crate) fn blank() -> Obj {
+        Obj { props: Properties::blank(), rect: Rect::new(0.0, 0.0, 0.0, 0.0), info: ObjData::blank(), gid: 0, poly: None, text: None, rotation: 0.0, point: false, ellipse: false, template: None, template_tileset: None}
     }
     
-    pub fn new(attribs : Vec<Attribute>, reader: Option<&mut Reader<&[u8]>>, path: &str) -> Result<Obj, TiledError> {
-        let mut obj = Obj::blank();
+    /// `path` is the directory templates are relative to
+    pub fn new(attribs : Vec<Attribute>, reader: Option<&mut Reader<&[u8]>>, path: &str, templates: &mut Templates) -> Result<Obj, TiledError> {
+        // start from the template so the instance's own attributes and children override it
+        let mut obj = match attribs.iter().find(|a| a.key.as_ref() == b"template") {
+            Some(a) => Obj::from_template(get_string(&a.value)?, path, templates)?,
+            None => Obj::blank(),
+        };
         obj.parse_attribs(attribs)?;
-        if let Some(template) = &obj.template {
-            let file = &read_file_to_string(&(path.to_owned() + "/" + template))?;
-            let mut reader = Reader::from_str(&file);
-            parse_xml(&mut obj, &mut reader)?;
-        }
         if let Some(reader) = reader {
             parse_xml(&mut obj, reader)?;
         }
         Ok(obj)
     }
-    
+
+    /// the tile a tile object shows, with its flips, `None` for objects that aren't tiles
+    pub fn tile(&self) -> Option<Gid> {
+        match self.gid {
+            0 => None,
+            gid => Some(Gid::new(gid)),
+        }
+    }
+
+    /// the area the object covers before it is rotated, tile objects are placed by their bottom left corner
+    pub fn bounds(&self) -> Rect {
+        match self.gid {
+            0 => self.rect,
+            _ => Rect::new(self.rect.x, self.rect.y - self.rect.h, self.rect.w, self.rect.h),
+        }
+    }
+
     fn parse_attribs(&mut self, attribs : Vec<Attribute>) -> Result<(), TiledError> {
         for a in attribs {
             match a.key.as_ref() {
@@ -50,8 +66,12 @@ impl Obj {
                 b"type" => self.info.type_name = get_string(&a.value)?.to_string(),
                 b"visible" => self.info.visible = get_string(&a.value)? == "1",
                 b"rotation" => self.rotation = get_value(&a.value)?,
+                b"gid" => {
+                    self.gid = get_value(&a.value)?;
+                    self.template_tileset = None;
+                },
                 b"template" => self.template = Some(get_string(&a.value)?.to_string()),
-                _ => println!("warning: unrecognized atrribute {:?}", a.key),
+                _ => unknown_attrib("object", &a),
             }
         }
         Ok(())
@@ -61,22 +81,19 @@ impl Obj {
 impl HandleXml for Obj {
     fn start(&mut self, e : &BytesStart, reader: &mut Reader<&[u8]>) -> Result<(), TiledError> {
         match e.name().as_ref() {
-            b"template" => (),
-            b"object" => self.parse_attribs(collect_attribs(&e)?)?,
             b"properties" => parse_xml(&mut self.props, reader)?,
             b"text" => self.text = Some(Box::new(Text::new(collect_attribs(&e)?, reader)?)),
-            _ => println!("unrecognized tag {:?}", e.name()),
+            _ => unknown_tag(Self::self_tag(), e),
         }
         Ok(())
     }
     fn empty(&mut self, e : &BytesStart) -> Result<(), TiledError> {
         match e.name().as_ref() {
-            b"object" => self.parse_attribs(collect_attribs(&e)?)?,
             b"polyline" => self.poly = Some(Box::new(Poly::new(collect_attribs(&e)?, false)?)),
             b"polygon" => self.poly = Some(Box::new(Poly::new(collect_attribs(&e)?, true)?)),
             b"ellipse" => self.ellipse = true,
             b"point" => self.point = true,
-            _ => println!("unrecognized empty tag {:?}", e.name()),
+            _ => unknown_tag(Self::self_tag(), e),
         }
         Ok(())
     }
@@ -86,7 +103,7 @@ impl HandleXml for Obj {
 }
 
 impl Poly {
-    fn blank() -> Poly {
+    pub(crate) fn blank() -> Poly {
         Poly { points: Vec::new(), obj: Obj::blank(), closed: false }
     }
     fn parse_attribs(&mut self, attribs : Vec<Attribute>, closed : bool) -> Result<(), TiledError> {
@@ -99,14 +116,14 @@ impl Poly {
                         let (x, y) = match pair.split_once(',') {
                             Some((x, y)) => match (x.parse(), y.parse()) {
                                 (Ok(x), Ok(y)) => (x, y),
-                                _ => { return Err(TiledError::ParseError(String::from("failed to parse poly points to floats"))); },
+                                _ => { return Err(TiledError::parse(String::from("failed to parse poly points to floats"))); },
                             },
-                            _ => { return Err(TiledError::MissingPoint()); },
+                            _ => { return Err(TiledError::new(ErrorKind::MissingPoint())); },
                         };
                         self.points.push(Vec2::new(x, y));
                     }
                 }
-                _ => println!("warning: unrecognized atrribute {:?}", a.key),
+                _ => unknown_attrib(if closed { "polygon" } else { "polyline" }, &a),
             }
         }
         Ok(())
@@ -119,7 +136,7 @@ impl Poly {
 }
 
 impl Text {
-    fn blank() -> Text {
+    pub(crate) fn blank() -> Text {
         Text {
             obj: Obj::blank(),
             text: String::new(),
@@ -138,25 +155,10 @@ impl Text {
                 b"wrap" => self.wrap = get_value::<i32>(&a.value)? == 1,
                 b"bold" => self.bold = get_value::<i32>(&a.value)? == 1,
                 b"italic" => self.italic = get_value::<i32>(&a.value)? == 1,
-                b"halign" => self.horizontal_align = match get_string(&a.value)? {
-                    "justify" => TextHorizontalAlign::Justify,
-                    "right" => TextHorizontalAlign::Right,
-                    "center" => TextHorizontalAlign::Center,
-                    "left" => TextHorizontalAlign::Left,
-                    _ => {
-                        return Err(TiledError::ParseError(String::from("text alignment not recognized")));
-                    },
-                },
-                b"valign" => self.vertical_align = match get_string(&a.value)? {
-                    "top" => TextVerticalAlign::Top,
-                    "bottom" => TextVerticalAlign::Bottom,
-                    "center" => TextVerticalAlign::Center,
-                    _ => {
-                        return Err(TiledError::ParseError(String::from("text alignment not recognized")));
-                    },
-                },
+                b"halign" => self.horizontal_align = TextHorizontalAlign::new(get_string(&a.value)?)?,
+                b"valign" => self.vertical_align = TextVerticalAlign::new(get_string(&a.value)?)?,
                 b"color" => self.colour = get_colour(&a.value)?,
-                _ => println!("warning: unrecognized atrribute {:?}", a.key),
+                _ => unknown_attrib("text", &a),
             }
         }
         Ok(())
@@ -169,11 +171,34 @@ impl Text {
     }
 }
 
+impl TextHorizontalAlign {
+    pub(crate) fn new(name: &str) -> Result<TextHorizontalAlign, TiledError> {
+        match name {
+            "justify" => Ok(TextHorizontalAlign::Justify),
+            "right" => Ok(TextHorizontalAlign::Right),
+            "center" => Ok(TextHorizontalAlign::Center),
+            "left" => Ok(TextHorizontalAlign::Left),
+            _ => Err(TiledError::parse(String::from("text alignment not recognized"))),
+        }
+    }
+}
+
+impl TextVerticalAlign {
+    pub(crate) fn new(name: &str) -> Result<TextVerticalAlign, TiledError> {
+        match name {
+            "top" => Ok(TextVerticalAlign::Top),
+            "bottom" => Ok(TextVerticalAlign::Bottom),
+            "center" => Ok(TextVerticalAlign::Center),
+            _ => Err(TiledError::parse(String::from("text alignment not recognized"))),
+        }
+    }
+}
+
 impl HandleXml for Text {
     fn text(&mut self, e : &BytesText) -> Result<(), TiledError> {
         let data = match e.unescape() {
             Ok(s) => s,
-            Err(_) => { return Err(TiledError::ParseError(String::from("text data could not be retrieved"))); },
+            Err(_) => { return Err(TiledError::parse(String::from("text data could not be retrieved"))); },
         };
         self.text = data.to_string();
         Ok(())
@@ -184,7 +209,7 @@ impl HandleXml for Text {
 }
 
 impl ObjGroup {    
-    fn blank() -> ObjGroup {
+    pub(crate) fn blank() -> ObjGroup {
         ObjGroup {
             props: Properties::blank(),
             objs: Vec::new(),
@@ -199,55 +224,75 @@ impl ObjGroup {
     fn parse_attribs(&mut self, attribs : Vec<Attribute>) -> Result<(), TiledError> {
         for a in attribs {
             if let Some(()) = self.info.handle_attrib(&a)? {
-                println!("warning: unrecognized atrribute {:?}", a.key);
+                unknown_attrib("objectgroup", &a);
             }
         }
         Ok(())
     }
-    pub fn new(attribs : Vec<Attribute>, reader: &mut Reader<&[u8]>, path: String) -> Result<ObjGroup, TiledError> {
+    pub fn new(attribs : Vec<Attribute>, reader: &mut Reader<&[u8]>, path: String, templates: &mut Templates) -> Result<ObjGroup, TiledError> {
         let mut og = ObjGroup::blank();
         og.path = path;
         og.parse_attribs(attribs)?;
-        parse_xml(&mut og, reader)?;
+        parse_xml(&mut ObjGroupParser { group: &mut og, templates }, reader)?;
+        og.sort_objs();
+        Ok(og)
+    }
+
+    /// every object in the group, whatever its shape
+    pub(crate) fn all_objs_mut(&mut self) -> impl Iterator<Item = &mut Obj> {
+        self.objs.iter_mut()
+            .chain(self.polys.iter_mut().map(|p| &mut p.obj))
+            .chain(self.text.iter_mut().map(|t| &mut t.obj))
+            .chain(self.ellipse.iter_mut())
+            .chain(self.points.iter_mut())
+    }
+
+    /// move polygons, text, ellipses and points out of `objs` into their own lists
+    pub(crate) fn sort_objs(&mut self) {
         let mut obj_list : Vec::<Obj> = Vec::new();
-        while og.objs.len() > 0{
-            let mut obj = og.objs.pop().unwrap();
+        while self.objs.len() > 0{
+            let mut obj = self.objs.pop().unwrap();
             if let Some(mut poly) = obj.poly.take() {
                 poly.obj = obj;
-                og.polys.push(*poly);
+                self.polys.push(*poly);
             } else {
                 if let Some(mut text) = obj.text.take() {
                     text.obj = obj;
-                    og.text.push(*text);
+                    self.text.push(*text);
                 } else {
                     if obj.ellipse {
-                        og.ellipse.push(obj);
+                        self.ellipse.push(obj);
                     } else if obj.point {
-                        og.points.push(obj);
+                        self.points.push(obj);
                     } else {
                         obj_list.push(obj);
                     }
                 }
             }
         }
-        og.objs = obj_list;
-        Ok(og)
+        self.objs = obj_list;
     }
 }
 
-impl HandleXml for ObjGroup {
+/// reads the contents of an object group, with the map's templates to hand
+struct ObjGroupParser<'a> {
+    group: &'a mut ObjGroup,
+    templates: &'a mut Templates,
+}
+
+impl HandleXml for ObjGroupParser<'_> {
     fn start(&mut self, e : &BytesStart, reader: &mut Reader<&[u8]>) -> Result<(), TiledError> {
         match e.name().as_ref() {
-            b"properties" => parse_xml(&mut self.props, reader)?,
-            b"object" => self.objs.push(Obj::new(collect_attribs(&e)?, Some(reader), &self.path)?),
-            _ => println!("unrecognized tag {:?}", e.name()),
+            b"properties" => parse_xml(&mut self.group.props, reader)?,
+            b"object" => self.group.objs.push(Obj::new(collect_attribs(e)?, Some(reader), &self.group.path, self.templates)?),
+            _ => unknown_tag(Self::self_tag(), e),
         }
         Ok(())
     }
     fn empty(&mut self, e : &BytesStart) -> Result<(), TiledError> {
         match e.name().as_ref() {
-             b"object" => self.objs.push(Obj::new(collect_attribs(&e)?, None, &self.path)?),
-            _ => println!("unrecognized empty tag {:?}", e.name()),
+             b"object" => self.group.objs.push(Obj::new(collect_attribs(e)?, None, &self.group.path, self.templates)?),
+            _ => unknown_tag(Self::self_tag(), e),
         }
         Ok(())
     }
@@ -0,0 +1,161 @@
+//! where tiles go in each of tiled's map orientations
+
+use super::{Map, Orientation, StaggerAxis, StaggerIndex};
+use geometry::{Rect, Vec2};
+
+/// the sizes tiled lays out staggered and hexagonal maps with,
+/// isometric staggered maps are hexagonal ones with no side length
+struct Stagger {
+    x_axis: bool,
+    even: bool,
+    tile_width: f64,
+    tile_height: f64,
+    side_length_x: f64,
+    side_length_y: f64,
+    /// distance between columns when staggered along x
+    column_width: f64,
+    /// distance between rows when staggered along y
+    row_height: f64,
+}
+
+impl Stagger {
+    fn new(map: &Map) -> Stagger {
+        // tiled rounds tile sizes down to even numbers so half tiles are whole pixels
+        let tile_width = (map.tile_width & !1) as f64;
+        let tile_height = (map.tile_height & !1) as f64;
+        let x_axis = matches!(map.stagger_axis, StaggerAxis::X);
+        let side_length = match map.orientation {
+            Orientation::HexagonalStaggered => map.hex_side_length as f64,
+            _ => 0.0,
+        };
+        let side_length_x = if x_axis { side_length } else { 0.0 };
+        let side_length_y = if x_axis { 0.0 } else { side_length };
+        Stagger {
+            x_axis,
+            even: matches!(map.stagger_index, StaggerIndex::Even),
+            tile_width,
+            tile_height,
+            side_length_x,
+            side_length_y,
+            column_width: (tile_width + side_length_x) / 2.0,
+            row_height: (tile_height + side_length_y) / 2.0,
+        }
+    }
+
+    /// if the row or column `i` along the stagger axis is shifted by half a tile
+    fn shifted(&self, i: i32) -> bool {
+        (i & 1 == 1) != self.even
+    }
+
+    fn tile_to_world(&self, x: i32, y: i32) -> Vec2 {
+        if self.x_axis {
+            let shift = if self.shifted(x) { self.row_height } else { 0.0 };
+            Vec2::new(x as f64 * self.column_width, y as f64 * (self.tile_height + self.side_length_y) + shift)
+        } else {
+            let shift = if self.shifted(y) { self.column_width } else { 0.0 };
+            Vec2::new(x as f64 * (self.tile_width + self.side_length_x) + shift, y as f64 * self.row_height)
+        }
+    }
+}
+
+impl Map {
+    /// the top left of the `tile_width` by `tile_height` box the tile at tile coordinates `x`, `y` is drawn in,
+    /// in isometric and hexagonal maps the boxes of neighbouring tiles overlap
+    pub fn tile_to_world(&self, x: i32, y: i32) -> Vec2 {
+        let (w, h) = (self.tile_width as f64, self.tile_height as f64);
+        match self.orientation {
+            Orientation::Orthogonal => Vec2::new(x as f64 * w, y as f64 * h),
+            // the map's top corner is at the top of the tile at 0, 0
+            Orientation::Isometric => Vec2::new(
+                (x - y + self.height as i32 - 1) as f64 * w / 2.0,
+                (x + y) as f64 * h / 2.0,
+            ),
+            Orientation::IsometricStaggered | Orientation::HexagonalStaggered => Stagger::new(self).tile_to_world(x, y),
+        }
+    }
+
+    /// where a point in an object group is in the world, isometric maps keep objects unprojected,
+    /// a tile height to a tile along each of the map's axes, the others keep them where they are drawn
+    pub fn object_to_world(&self, pos: Vec2) -> Vec2 {
+        match self.orientation {
+            Orientation::Isometric => {
+                let (w, h) = (self.tile_width as f64, self.tile_height as f64);
+                let (x, y) = (pos.x / h, pos.y / h);
+                // from the map's top corner, like `tile_to_world`
+                Vec2::new((x - y + self.height as f64) * w / 2.0, (x + y) * h / 2.0)
+            },
+            _ => pos,
+        }
+    }
+
+    /// the box the tile at tile coordinates `x`, `y` is drawn in
+    pub fn tile_rect(&self, x: i32, y: i32) -> Rect {
+        let pos = self.tile_to_world(x, y);
+        Rect::new(pos.x, pos.y, self.tile_width as f64, self.tile_height as f64)
+    }
+
+    /// the tile coordinates of the tile whose shape a point in the map is inside
+    pub fn world_to_tile(&self, pos: Vec2) -> (i32, i32) {
+        let (w, h) = (self.tile_width as f64, self.tile_height as f64);
+        match self.orientation {
+            Orientation::Orthogonal => ((pos.x / w).floor() as i32, (pos.y / h).floor() as i32),
+            Orientation::Isometric => {
+                let x = (pos.x - self.height as f64 * w / 2.0) / w;
+                let y = pos.y / h;
+                ((y + x).floor() as i32, (y - x).floor() as i32)
+            },
+            Orientation::IsometricStaggered | Orientation::HexagonalStaggered => {
+                let s = Stagger::new(self);
+                let (x, y) = if s.x_axis {
+                    (pos.x / s.column_width, pos.y / (s.tile_height + s.side_length_y))
+                } else {
+                    (pos.x / (s.tile_width + s.side_length_x), pos.y / s.row_height)
+                };
+                let (x, y) = (x.floor() as i32, y.floor() as i32);
+                // the guess is at most a tile off, so pick the neighbour with the closest centre,
+                // measured so that the closest diamond is the one the point is in
+                let hex = matches!(self.orientation, Orientation::HexagonalStaggered);
+                let mut closest = (x, y);
+                let mut closest_distance = f64::INFINITY;
+                for ty in y - 1..=y + 1 {
+                    for tx in x - 1..=x + 1 {
+                        let centre = s.tile_to_world(tx, ty);
+                        let dx = pos.x - (centre.x + s.tile_width / 2.0);
+                        let dy = pos.y - (centre.y + s.tile_height / 2.0);
+                        let distance = match hex {
+                            true => dx * dx + dy * dy,
+                            false => (dx / s.tile_width).abs() + (dy / s.tile_height).abs(),
+                        };
+                        if distance < closest_distance {
+                            closest = (tx, ty);
+                            closest_distance = distance;
+                        }
+                    }
+                }
+                closest
+            },
+        }
+    }
+
+    /// the pixel area covered by the tiles from `min_x`, `min_y` up to but not including `max_x`, `max_y`
+    pub(crate) fn tiles_bounds(&self, min_x: i32, min_y: i32, max_x: i32, max_y: i32) -> Rect {
+        if min_x >= max_x || min_y >= max_y {
+            return Rect::blank();
+        }
+        let (mut left, mut top, mut right, mut bottom) = (f64::MAX, f64::MAX, f64::MIN, f64::MIN);
+        // only the two outermost rows and columns can reach the edge, as staggering shifts every other one in
+        for y in min_y..max_y {
+            let edge_row = y < min_y + 2 || y >= max_y - 2;
+            let mut x = min_x;
+            while x < max_x {
+                let r = self.tile_rect(x, y);
+                left = left.min(r.x);
+                top = top.min(r.y);
+                right = right.max(r.x + r.w);
+                bottom = bottom.max(r.y + r.h);
+                x = if !edge_row && x == min_x + 1 { (max_x - 2).max(x + 1) } else { x + 1 };
+            }
+        }
+        Rect::new(left, top, right - left, bottom - top)
+    }
+}
@@ -1,71 +1,150 @@
 use std::collections::HashMap;
 
-use super::Properties;
+use super::{Properties, ClassProperty};
 use super::helper::*;
-use super::TiledError;
+use super::{TiledError, ErrorKind};
 
-use quick_xml::events::BytesStart;
+use quick_xml::events::{BytesStart, BytesText};
 use quick_xml::events::attributes::Attribute;
+use quick_xml::reader::Reader;
 
 enum PropertyType {
     Bool,
     Int,
+    Float,
+    String,
+    Colour,
+    File,
+    Object,
+    Class,
+}
+
+/// the contents of a `property` start tag, multi-line strings are stored
+/// as text and class properties hold their members in a nested `properties`
+struct PropertyContent {
+    text : String,
+    props : Properties,
 }
 
 impl Properties {
     pub fn blank() -> Properties {
-        Properties { booleans: HashMap::new(), integers: HashMap::new(), }
+        Properties {
+            booleans: HashMap::new(),
+            integers: HashMap::new(),
+            floats: HashMap::new(),
+            strings: HashMap::new(),
+            colours: HashMap::new(),
+            files: HashMap::new(),
+            objects: HashMap::new(),
+            classes: HashMap::new(),
+        }
     }
-    
-    fn add_property(&mut self, attribs : Vec<Attribute>) -> Result<(), TiledError>{
+
+    fn add_property(&mut self, attribs : Vec<Attribute>, content: Option<PropertyContent>) -> Result<(), TiledError>{
         let mut name = String::new();
-        let mut prop_type : Option<PropertyType> = None;
+        // tiled leaves out the type for string properties
+        let mut prop_type = PropertyType::String;
+        let mut class = String::new();
+        let mut value = None;
         for a in attribs {
              match a.key.as_ref() {
                  b"name" => name = get_string(&a.value)?.to_string(),
                  b"type" => prop_type = match get_string(&a.value)? {
-                     "bool" => Some(PropertyType::Bool),
-                     "int" => Some(PropertyType::Int),
-                     _ =>  {
-                         println!("warning: unrecognized type {:?}", get_string(&a.value)?);
-                         None
-                     }, 
-                 },
-                 b"value" => match prop_type {
-                     Some(t) => {
-                         match t {
-                             PropertyType::Bool => {
-                                 self.booleans
-                                     .insert(name,
-                                             match get_string(&a.value)? {
-                                                 "true" => true,
-                                                 "false" => false,
-                                                 _ => { return Err(TiledError::ParseError(
-                                                     String::from(
-                                                         "bool didnt have true or false value"
-                                                     )));
-                                                 },
-                                             }
-                                     );
-                             },
-                             PropertyType::Int => { self.integers.insert(name, get_value(&a.value)?); },
-                         };
-                         break;
+                     "bool" => PropertyType::Bool,
+                     "int" => PropertyType::Int,
+                     "float" => PropertyType::Float,
+                     "string" => PropertyType::String,
+                     "color" => PropertyType::Colour,
+                     "file" => PropertyType::File,
+                     "object" => PropertyType::Object,
+                     "class" => PropertyType::Class,
+                     t =>  {
+                         return Err(TiledError::new(ErrorKind::UnsupportedType(t.to_string())));
                      },
-                     None => { return Err(TiledError::UnsupportedType()); },
-                 }
-                 _ => println!("warning: unrecognized atrribute {:?}", a.key),
+                 },
+                 b"propertytype" => class = get_string(&a.value)?.to_string(),
+                 b"value" => value = Some(a),
+                 _ => unknown_attrib("property", &a),
              }
         }
+        let (text, props) = match content {
+            Some(c) => (c.text, c.props),
+            None => (String::new(), Properties::blank()),
+        };
+        let attrib = match value {
+            Some(a) => a,
+            None => match prop_type {
+                PropertyType::String => {
+                    self.strings.insert(name, text);
+                    return Ok(());
+                },
+                PropertyType::Class => {
+                    self.classes.insert(name, ClassProperty { class, props });
+                    return Ok(());
+                },
+                _ => { return Err(TiledError::parse(
+                    format!("property {:?} had no value", name)
+                )); },
+            },
+        };
+        let value = &attrib.value;
+        match prop_type {
+            PropertyType::Bool => {
+                self.booleans
+                    .insert(name,
+                            match get_string(value)? {
+                                "true" => true,
+                                "false" => false,
+                                _ => { return Err(TiledError::parse(
+                                    String::from(
+                                        "bool didnt have true or false value"
+                                    )));
+                                },
+                            }
+                    );
+            },
+            PropertyType::Int => { self.integers.insert(name, get_value(value)?); },
+            PropertyType::Float => { self.floats.insert(name, get_value(value)?); },
+            PropertyType::String => { self.strings.insert(name, unescape_string(&attrib)?); },
+            PropertyType::Colour => {
+                // an unset colour is saved with an empty value
+                let colour = match get_string(value)? {
+                    "" => super::Colour { r: 0, g: 0, b: 0, a: 0 },
+                    _ => get_colour(value)?,
+                };
+                self.colours.insert(name, colour);
+            },
+            PropertyType::File => { self.files.insert(name, unescape_string(&attrib)?); },
+            PropertyType::Object => { self.objects.insert(name, get_value(value)?); },
+            PropertyType::Class => { self.classes.insert(name, ClassProperty { class, props }); },
+        }
         Ok(())
     }
 }
 
+fn unescape_string(a : &Attribute) -> Result<String, TiledError> {
+    match a.unescape_value() {
+        Ok(s) => Ok(s.to_string()),
+        Err(_) => Err(TiledError::new(ErrorKind::ParseBytesError())),
+    }
+}
+
 impl HandleXml for Properties {
+    fn start(&mut self, e : &BytesStart, reader: &mut Reader<&[u8]>) -> Result<(), TiledError> {
+        match e.name().as_ref() {
+            b"property" => {
+                let mut content = PropertyContent { text: String::new(), props: Properties::blank() };
+                parse_xml(&mut content, reader)?;
+                self.add_property(collect_attribs(e)?, Some(content))?
+            },
+            _ => unknown_tag(Self::self_tag(), e),
+        }
+        Ok(())
+    }
     fn empty(&mut self, e : &BytesStart) -> Result<(), TiledError> {
         match e.name().as_ref() {
-            b"property" => self.add_property(collect_attribs(&e)?)?,
-            _ => println!("unrecognized empty tag {:?}", e.name()),
+            b"property" => self.add_property(collect_attribs(&e)?, None)?,
+            _ => unknown_tag(Self::self_tag(), e),
         }
         Ok(())
     }
@@ -73,3 +152,23 @@ impl HandleXml for Properties {
         "properties"
     }
 }
+
+impl HandleXml for PropertyContent {
+    fn start(&mut self, e : &BytesStart, reader: &mut Reader<&[u8]>) -> Result<(), TiledError> {
+        match e.name().as_ref() {
+            b"properties" => parse_xml(&mut self.props, reader)?,
+            _ => unknown_tag(Self::self_tag(), e),
+        }
+        Ok(())
+    }
+    fn text(&mut self, e : &BytesText) -> Result<(), TiledError> {
+        match e.unescape() {
+            Ok(s) => self.text.push_str(&s),
+            Err(_) => { return Err(TiledError::parse(String::from("property text could not be retrieved"))); },
+        };
+        Ok(())
+    }
+    fn self_tag() -> &'static str {
+        "property"
+    }
+}
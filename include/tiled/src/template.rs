@@ -0,0 +1,106 @@
+use super::{Map, Obj, Templates, TiledError, helper::*};
+use super::{FLIPPED_HORIZONTALLY, FLIPPED_VERTICALLY, FLIPPED_DIAGONALLY, ROTATED_HEXAGONAL_120};
+
+use quick_xml::events::BytesStart;
+use quick_xml::reader::Reader;
+
+/// the contents of a template file
+pub(crate) struct TemplateFile {
+    pub(crate) obj: Obj,
+    /// the first gid and source of the tileset the object's `gid` counts from
+    pub(crate) tileset: Option<(u32, String)>,
+    /// the directory of the template, which paths in it are relative to
+    pub(crate) dir: String,
+}
+
+impl HandleXml for TemplateFile {
+    fn start(&mut self, e : &BytesStart, reader: &mut Reader<&[u8]>) -> Result<(), TiledError> {
+        match e.name().as_ref() {
+            b"template" => (),
+            b"object" => self.obj = Obj::new(collect_attribs(e)?, Some(reader), &self.dir, &mut Templates::new())?,
+            _ => unknown_tag(Self::self_tag(), e),
+        }
+        Ok(())
+    }
+    fn empty(&mut self, e : &BytesStart) -> Result<(), TiledError> {
+        match e.name().as_ref() {
+            b"object" => self.obj = Obj::new(collect_attribs(e)?, None, &self.dir, &mut Templates::new())?,
+            b"tileset" => {
+                let mut first_gid = 1;
+                let mut source = String::new();
+                for a in collect_attribs(e)? {
+                    match a.key.as_ref() {
+                        b"firstgid" => first_gid = get_value(&a.value)?,
+                        b"source" => source = get_string(&a.value)?.to_string(),
+                        _ => unknown_attrib("tileset", &a),
+                    }
+                }
+                self.tileset = Some((first_gid, source));
+            },
+            _ => unknown_tag(Self::self_tag(), e),
+        }
+        Ok(())
+    }
+    fn self_tag() -> &'static str {
+        "template"
+    }
+}
+
+impl Obj {
+    /// a copy of the object in a template file, in tiled's xml or json format,
+    /// `path` is the directory the template is relative to
+    pub(crate) fn from_template(template: &str, path: &str, templates: &mut Templates) -> Result<Obj, TiledError> {
+        let file = join_path(path, template);
+        if let Some(obj) = templates.get(&file) {
+            return Ok(obj.clone());
+        }
+        let dir = match file.rsplit_once('/') {
+            Some((dir, _)) => dir.to_string(),
+            None => String::new(),
+        };
+        let mut t = TemplateFile { obj: Obj::blank(), tileset: None, dir };
+        parse_file(&file, |text| {
+            if is_json(&file) {
+                super::json::parse_template(&mut t, text)
+            } else {
+                parse_xml(&mut t, &mut Reader::from_str(text))
+            }
+        })?;
+        if let Some((first_gid, source)) = t.tileset {
+            if t.obj.gid != 0 {
+                t.obj.template_tileset = Some((first_gid, join_path(&t.dir, &source)));
+            }
+        }
+        templates.insert(file, t.obj.clone());
+        Ok(t.obj)
+    }
+}
+
+impl Map {
+    /// move the gids of tile objects that came from templates into the
+    /// map's gids, by finding the template's tileset among the map's
+    pub(crate) fn resolve_template_tilesets(&mut self) -> Result<(), TiledError> {
+        const FLAGS: u32 = FLIPPED_HORIZONTALLY | FLIPPED_VERTICALLY | FLIPPED_DIAGONALLY | ROTATED_HEXAGONAL_120;
+        for og in self.obj_groups.iter_mut() {
+            for obj in og.all_objs_mut() {
+                let (first_gid, file) = match obj.template_tileset.take() {
+                    Some(t) => t,
+                    None => continue,
+                };
+                let ts = self.tilesets.iter().find(|ts| match &ts.source {
+                    Some(source) => join_path(&self.path, source) == file,
+                    None => false,
+                });
+                let ts = match ts {
+                    Some(ts) => ts,
+                    None => {
+                        return Err(TiledError::parse(format!("template tileset {} is not in the map", file)));
+                    },
+                };
+                let id = (obj.gid & !FLAGS).saturating_sub(first_gid);
+                obj.gid = (ts.first_tile_id + id) | (obj.gid & FLAGS);
+            }
+        }
+        Ok(())
+    }
+}
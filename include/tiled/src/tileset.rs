@@ -1,13 +1,16 @@
 use super::helper::*;
 use super::error::TiledError;
-use super::Tileset;
+use super::{Tileset, Tile, Frame, ObjGroup, Properties, Templates};
+use super::wang::{Terrains, WangSets, parse_terrain_corners};
+
+use std::collections::HashMap;
 
 use quick_xml::events::attributes::Attribute;
 use quick_xml::events::BytesStart;
 use quick_xml::reader::Reader;
 
 impl Tileset {
-    fn blank() -> Tileset {
+    pub(crate) fn blank() -> Tileset {
         Tileset {
             first_tile_id : 0,
             name : String::from(""),
@@ -22,6 +25,11 @@ impl Tileset {
             image_height : 0,
             version : String::new(),
             tiledversion : String::new(),
+            tiles : HashMap::new(),
+            terrains : Vec::new(),
+            wang_sets : Vec::new(),
+            source : None,
+            path : String::new(),
             }
     }
 
@@ -37,7 +45,7 @@ impl Tileset {
                 b"columns" => self.column_count = get_value(&a.value)?,
                 b"version" => self.version = get_string(&a.value)?.to_string(),
                 b"tiledversion" => self.tiledversion = get_string(&a.value)?.to_string(),
-                _ => println!("warning: unrecognized attribute {:?}", a.key),
+                _ => unknown_attrib("tileset", &a),
             }
         }
         Ok(())
@@ -46,56 +54,91 @@ impl Tileset {
     fn parse_image_attribs(&mut self, attribs : Vec<Attribute>) -> Result<(), TiledError> {
         for a in attribs {
             match a.key.as_ref() {
-                b"source" => self.image_path.push_str(get_string(&a.value)?),
+                b"source" => self.image_path = join_path(&self.path, get_string(&a.value)?),
                 b"width" => self.image_width = get_value(&a.value)?,
                 b"height" => self.image_height = get_value(&a.value)?,
-                _ => println!("warning: unrecognized attribute {:?}", a.key),
+                _ => unknown_attrib("image", &a),
             }
         }
         Ok(())
     }
     
-    fn parse_xml(tileset : &mut Self, tsx_text : String) -> Result<(), TiledError>{
-        let mut reader = Reader::from_str(&tsx_text);
+    fn parse_xml(tileset : &mut Self, tsx_text : &str) -> Result<(), TiledError>{
+        let mut reader = Reader::from_str(tsx_text);
         parse_xml(tileset, &mut reader)
     }
     
-    pub fn new(attribs : Vec<Attribute>, path : String) -> Result<Tileset, TiledError> {
-        let mut tmx_path = path.clone();
+    /// `reader` is given for a tileset embedded in the map, and is read up to the closing tag
+    pub fn new(attribs : Vec<Attribute>, path : String, reader: Option<&mut Reader<&[u8]>>) -> Result<Tileset, TiledError> {
         let mut tileset = Self::blank();
-        tileset.image_path = path;
+        tileset.path = path;
+        let mut embedded = Vec::new();
         for a in attribs {
             match a.key.as_ref() {
                 b"firstgid" => tileset.first_tile_id = get_value(&a.value)?,
-                b"source" => {
-                    Self::parse_xml(
-                        &mut tileset,
-                        read_file_to_string( {
-                            tmx_path.push_str(get_string(&a.value)?);
-                            &tmx_path
-                        })?
-                    )?;
-                }
-                _  => println!("warning: unrecognized atrribute {:?}", a.key),
+                b"source" => tileset.load_source(get_string(&a.value)?)?,
+                _  => embedded.push(a),
             }
         }
+        tileset.parse_tileset_attribs(embedded)?;
+        if let Some(reader) = reader {
+            parse_xml(&mut tileset, reader)?;
+        }
         
         Ok(tileset)
     }
+
+    /// read an external tileset file, in tiled's xml or json format, relative to `path`
+    pub(crate) fn load_source(&mut self, source: &str) -> Result<(), TiledError> {
+        self.source = Some(source.to_string());
+        let ts_path = join_path(&self.path, source);
+        // paths in the tileset are relative to the tileset file
+        self.path = match ts_path.rsplit_once('/') {
+            Some((dir, _)) => dir.to_owned() + "/",
+            None => String::new(),
+        };
+        parse_file(&ts_path, |text| {
+            if is_json(source) {
+                super::json::parse_tileset_file(self, text)
+            } else {
+                Self::parse_xml(self, text)
+            }
+        })
+    }
+
+    /// one more than the largest tile id in the tileset, image collections can skip ids
+    pub fn id_count(&self) -> u32 {
+        match self.tiles.keys().max() {
+            Some(id) => self.tile_count.max(id + 1),
+            None => self.tile_count,
+        }
+    }
 }
 
 impl HandleXml for Tileset {
-    fn start(&mut self, e : &BytesStart, _: &mut Reader<&[u8]>) -> Result<(), TiledError> {
+    fn start(&mut self, e : &BytesStart, reader: &mut Reader<&[u8]>) -> Result<(), TiledError> {
         match e.name().as_ref() {
             b"tileset" => self.parse_tileset_attribs(collect_attribs(&e)?)?,
-            _ => println!("unrecognized tag {:?}", e.name()),
+            b"tile" => {
+                let tile = Tile::new(collect_attribs(e)?, Some(reader), &self.path)?;
+                self.tiles.insert(tile.id, tile);
+            },
+            b"terraintypes" => parse_xml(&mut Terrains(&mut self.terrains), reader)?,
+            b"wangsets" => parse_xml(&mut WangSets(&mut self.wang_sets), reader)?,
+            _ => unknown_tag(Self::self_tag(), e),
         }
         Ok(())
     }
     fn empty(&mut self, e : &BytesStart) -> Result<(), TiledError> {
         match e.name().as_ref() {
             b"image" => self.parse_image_attribs(collect_attribs(&e)?)?,
-            _ => println!("unrecognized empty tag {:?}", e.name()),
+            // only affects how tiles are shown in the editor
+            b"grid" => (),
+            b"tile" => {
+                let tile = Tile::new(collect_attribs(e)?, None, &self.path)?;
+                self.tiles.insert(tile.id, tile);
+            },
+            _ => unknown_tag(Self::self_tag(), e),
         }
         Ok(())
     }
@@ -103,3 +146,107 @@ impl HandleXml for Tileset {
         "tileset"
     }
 }
+
+impl Tile {
+    pub(crate) fn blank(path: &str) -> Tile {
+        Tile {
+            id: 0,
+            type_name: String::new(),
+            props: Properties::blank(),
+            collision: None,
+            animation: Vec::new(),
+            image_path: String::new(),
+            image_width: 0,
+            image_height: 0,
+            terrain: [None; 4],
+            probability: 1.0,
+            path: path.to_string(),
+        }
+    }
+
+    fn new(attribs : Vec<Attribute>, reader: Option<&mut Reader<&[u8]>>, path: &str) -> Result<Tile, TiledError> {
+        let mut tile = Tile::blank(path);
+        for a in attribs {
+            match a.key.as_ref() {
+                b"id" => tile.id = get_value(&a.value)?,
+                b"type" | b"class" => tile.type_name = get_string(&a.value)?.to_string(),
+                b"terrain" => tile.terrain = parse_terrain_corners(get_string(&a.value)?)?,
+                b"probability" => tile.probability = get_value(&a.value)?,
+                _ => unknown_attrib("tile", &a),
+            }
+        }
+        if let Some(reader) = reader {
+            parse_xml(&mut tile, reader)?;
+        }
+        Ok(tile)
+    }
+
+    /// the objects a tile collides with can't come from templates, as a template's tile objects
+    /// take their gids from a map's tilesets and a tileset can be used by many maps
+    pub(crate) fn set_collision(&mut self, mut og: ObjGroup) -> Result<(), TiledError> {
+        if let Some(o) = og.all_objs_mut().find(|o| o.template.is_some()) {
+            return Err(TiledError::parse(format!(
+                "tile {} collision uses template {}, templates can't be used in tilesets",
+                self.id, o.template.as_deref().unwrap_or(""),
+            )));
+        }
+        self.collision = Some(og);
+        Ok(())
+    }
+}
+
+impl HandleXml for Tile {
+    fn start(&mut self, e : &BytesStart, reader: &mut Reader<&[u8]>) -> Result<(), TiledError> {
+        match e.name().as_ref() {
+            b"properties" => parse_xml(&mut self.props, reader)?,
+            b"objectgroup" => self.set_collision(ObjGroup::new(collect_attribs(e)?, reader, self.path.clone(), &mut Templates::new())?)?,
+            b"animation" => parse_xml(&mut Animation(&mut self.animation), reader)?,
+            _ => unknown_tag(Self::self_tag(), e),
+        }
+        Ok(())
+    }
+    fn empty(&mut self, e : &BytesStart) -> Result<(), TiledError> {
+        match e.name().as_ref() {
+            b"image" => {
+                for a in collect_attribs(e)? {
+                    match a.key.as_ref() {
+                        b"source" => self.image_path = join_path(&self.path, get_string(&a.value)?),
+                        b"width" => self.image_width = get_value(&a.value)?,
+                        b"height" => self.image_height = get_value(&a.value)?,
+                        _ => unknown_attrib("image", &a),
+                    }
+                }
+            },
+            _ => unknown_tag(Self::self_tag(), e),
+        }
+        Ok(())
+    }
+    fn self_tag() -> &'static str {
+        "tile"
+    }
+}
+
+struct Animation<'a>(&'a mut Vec<Frame>);
+
+impl HandleXml for Animation<'_> {
+    fn empty(&mut self, e : &BytesStart) -> Result<(), TiledError> {
+        match e.name().as_ref() {
+            b"frame" => {
+                let mut frame = Frame { tile_id: 0, duration: 0 };
+                for a in collect_attribs(e)? {
+                    match a.key.as_ref() {
+                        b"tileid" => frame.tile_id = get_value(&a.value)?,
+                        b"duration" => frame.duration = get_value(&a.value)?,
+                        _ => unknown_attrib("frame", &a),
+                    }
+                }
+                self.0.push(frame);
+            },
+            _ => unknown_tag(Self::self_tag(), e),
+        }
+        Ok(())
+    }
+    fn self_tag() -> &'static str {
+        "animation"
+    }
+}
@@ -0,0 +1,301 @@
+//! terrains and wang sets, which say how tiles join up so levels can be auto-tiled
+
+use super::helper::*;
+use super::error::TiledError;
+use super::{Map, Tileset, Terrain, WangSet, WangSetType, WangColour, WangId, Properties, Colour, Gid};
+
+use std::collections::HashMap;
+
+use quick_xml::events::attributes::Attribute;
+use quick_xml::events::BytesStart;
+use quick_xml::reader::Reader;
+
+/// the terrain at each corner of a tile, top left, top right, bottom left then bottom right,
+/// given by the name of its wang colour or terrain, `None` where there is none
+pub type Corners<'a> = [Option<&'a str>; 4];
+
+/// tiled uses -1 for no tile
+pub(crate) fn tile_index(index: i64) -> Option<u32> {
+    u32::try_from(index).ok()
+}
+
+/// a tile's `terrain` attribute, like `0,0,,1`
+pub(crate) fn parse_terrain_corners(text: &str) -> Result<[Option<u32>; 4], TiledError> {
+    let mut corners = [None; 4];
+    let parts: Vec<&str> = text.split(',').collect();
+    if parts.len() != 4 {
+        return Err(TiledError::parse(format!("tile terrain {:?} doesn't have 4 corners", text)));
+    }
+    for (corner, part) in corners.iter_mut().zip(parts) {
+        if !part.is_empty() {
+            *corner = Some(part.parse().map_err(|_| TiledError::parse(format!("bad terrain index {:?}", part)))?);
+        }
+    }
+    Ok(corners)
+}
+
+impl WangSetType {
+    pub(crate) fn new(name: &str) -> Result<WangSetType, TiledError> {
+        match name {
+            "corner" => Ok(WangSetType::Corner),
+            "edge" => Ok(WangSetType::Edge),
+            "mixed" => Ok(WangSetType::Mixed),
+            _ => Err(TiledError::parse(format!("unrecognized wang set type {:?}", name))),
+        }
+    }
+
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            WangSetType::Corner => "corner",
+            WangSetType::Edge => "edge",
+            WangSetType::Mixed => "mixed",
+        }
+    }
+}
+
+impl WangId {
+    /// a `wangid` attribute, like `0,1,0,1,0,1,0,1`
+    pub(crate) fn parse(text: &str) -> Result<WangId, TiledError> {
+        let mut id = [0; 8];
+        let parts: Vec<&str> = text.split(',').collect();
+        if parts.len() != 8 {
+            return Err(TiledError::parse(format!("wang id {:?} doesn't have 8 colours", text)));
+        }
+        for (colour, part) in id.iter_mut().zip(parts) {
+            *colour = part.trim().parse().map_err(|_| TiledError::parse(format!("bad wang colour {:?}", part)))?;
+        }
+        Ok(WangId(id))
+    }
+
+    /// the colours at the top left, top right, bottom left and bottom right corners
+    pub fn corners(&self) -> [u8; 4] {
+        [self.0[7], self.0[1], self.0[5], self.0[3]]
+    }
+
+    /// the colours at the top, right, bottom and left edges
+    pub fn edges(&self) -> [u8; 4] {
+        [self.0[0], self.0[2], self.0[4], self.0[6]]
+    }
+}
+
+impl WangSet {
+    pub(crate) fn blank() -> WangSet {
+        WangSet {
+            name: String::new(),
+            set_type: WangSetType::Corner,
+            tile: None,
+            colours: Vec::new(),
+            tiles: HashMap::new(),
+            props: Properties::blank(),
+        }
+    }
+
+    fn new(attribs: Vec<Attribute>, reader: &mut Reader<&[u8]>) -> Result<WangSet, TiledError> {
+        let mut set = WangSet::blank();
+        for a in attribs {
+            match a.key.as_ref() {
+                b"name" => set.name = get_string(&a.value)?.to_string(),
+                b"type" => set.set_type = WangSetType::new(get_string(&a.value)?)?,
+                b"tile" => set.tile = tile_index(get_value(&a.value)?),
+                _ => unknown_attrib("wangset", &a),
+            }
+        }
+        parse_xml(&mut set, reader)?;
+        Ok(set)
+    }
+
+    /// the name of a colour from a `WangId`, `None` for 0
+    pub fn colour_name(&self, colour: u8) -> Option<&str> {
+        match colour {
+            0 => None,
+            c => self.colours.get(c as usize - 1).map(|c| c.name.as_str()),
+        }
+    }
+}
+
+impl HandleXml for WangSet {
+    fn start(&mut self, e : &BytesStart, reader: &mut Reader<&[u8]>) -> Result<(), TiledError> {
+        match e.name().as_ref() {
+            b"properties" => parse_xml(&mut self.props, reader)?,
+            b"wangcolor" => self.colours.push(WangColour::new(collect_attribs(e)?, Some(reader))?),
+            _ => unknown_tag(Self::self_tag(), e),
+        }
+        Ok(())
+    }
+    fn empty(&mut self, e : &BytesStart) -> Result<(), TiledError> {
+        match e.name().as_ref() {
+            b"wangcolor" => self.colours.push(WangColour::new(collect_attribs(e)?, None)?),
+            b"wangtile" => {
+                let mut tile_id = 0;
+                let mut wang_id = WangId([0; 8]);
+                for a in collect_attribs(e)? {
+                    match a.key.as_ref() {
+                        b"tileid" => tile_id = get_value(&a.value)?,
+                        b"wangid" => wang_id = WangId::parse(get_string(&a.value)?)?,
+                        _ => unknown_attrib("wangtile", &a),
+                    }
+                }
+                self.tiles.insert(tile_id, wang_id);
+            },
+            _ => unknown_tag(Self::self_tag(), e),
+        }
+        Ok(())
+    }
+    fn self_tag() -> &'static str {
+        "wangset"
+    }
+}
+
+impl WangColour {
+    pub(crate) fn blank() -> WangColour {
+        WangColour {
+            name: String::new(),
+            colour: Colour { r: 255, g: 255, b: 255, a: 255 },
+            tile: None,
+            probability: 1.0,
+            props: Properties::blank(),
+        }
+    }
+
+    fn new(attribs: Vec<Attribute>, reader: Option<&mut Reader<&[u8]>>) -> Result<WangColour, TiledError> {
+        let mut colour = WangColour::blank();
+        for a in attribs {
+            match a.key.as_ref() {
+                b"name" => colour.name = get_string(&a.value)?.to_string(),
+                b"color" => colour.colour = get_colour(&a.value)?,
+                b"tile" => colour.tile = tile_index(get_value(&a.value)?),
+                b"probability" => colour.probability = get_value(&a.value)?,
+                _ => unknown_attrib("wangcolor", &a),
+            }
+        }
+        if let Some(reader) = reader {
+            parse_xml(&mut colour, reader)?;
+        }
+        Ok(colour)
+    }
+}
+
+impl HandleXml for WangColour {
+    fn start(&mut self, e : &BytesStart, reader: &mut Reader<&[u8]>) -> Result<(), TiledError> {
+        match e.name().as_ref() {
+            b"properties" => parse_xml(&mut self.props, reader)?,
+            _ => unknown_tag(Self::self_tag(), e),
+        }
+        Ok(())
+    }
+    fn self_tag() -> &'static str {
+        "wangcolor"
+    }
+}
+
+pub(crate) struct WangSets<'a>(pub(crate) &'a mut Vec<WangSet>);
+
+impl HandleXml for WangSets<'_> {
+    fn start(&mut self, e : &BytesStart, reader: &mut Reader<&[u8]>) -> Result<(), TiledError> {
+        match e.name().as_ref() {
+            b"wangset" => self.0.push(WangSet::new(collect_attribs(e)?, reader)?),
+            _ => unknown_tag(Self::self_tag(), e),
+        }
+        Ok(())
+    }
+    fn self_tag() -> &'static str {
+        "wangsets"
+    }
+}
+
+impl Terrain {
+    pub(crate) fn blank() -> Terrain {
+        Terrain {
+            name: String::new(),
+            tile: None,
+            props: Properties::blank(),
+        }
+    }
+
+    fn new(attribs: Vec<Attribute>, reader: Option<&mut Reader<&[u8]>>) -> Result<Terrain, TiledError> {
+        let mut terrain = Terrain::blank();
+        for a in attribs {
+            match a.key.as_ref() {
+                b"name" => terrain.name = get_string(&a.value)?.to_string(),
+                b"tile" => terrain.tile = tile_index(get_value(&a.value)?),
+                _ => unknown_attrib("terrain", &a),
+            }
+        }
+        if let Some(reader) = reader {
+            parse_xml(&mut terrain, reader)?;
+        }
+        Ok(terrain)
+    }
+}
+
+impl HandleXml for Terrain {
+    fn start(&mut self, e : &BytesStart, reader: &mut Reader<&[u8]>) -> Result<(), TiledError> {
+        match e.name().as_ref() {
+            b"properties" => parse_xml(&mut self.props, reader)?,
+            _ => unknown_tag(Self::self_tag(), e),
+        }
+        Ok(())
+    }
+    fn self_tag() -> &'static str {
+        "terrain"
+    }
+}
+
+pub(crate) struct Terrains<'a>(pub(crate) &'a mut Vec<Terrain>);
+
+impl HandleXml for Terrains<'_> {
+    fn start(&mut self, e : &BytesStart, reader: &mut Reader<&[u8]>) -> Result<(), TiledError> {
+        match e.name().as_ref() {
+            b"terrain" => self.0.push(Terrain::new(collect_attribs(e)?, Some(reader))?),
+            _ => unknown_tag(Self::self_tag(), e),
+        }
+        Ok(())
+    }
+    fn empty(&mut self, e : &BytesStart) -> Result<(), TiledError> {
+        match e.name().as_ref() {
+            b"terrain" => self.0.push(Terrain::new(collect_attribs(e)?, None)?),
+            _ => unknown_tag(Self::self_tag(), e),
+        }
+        Ok(())
+    }
+    fn self_tag() -> &'static str {
+        "terraintypes"
+    }
+}
+
+impl Tileset {
+    /// the terrain at each corner of tile `id`, from the first wang set with corners that has the tile,
+    /// or else the tile's old style terrain, `None` if neither knows the tile
+    pub fn corners(&self, id: u32) -> Option<Corners<'_>> {
+        let wang = self.wang_sets.iter()
+            .filter(|set| !matches!(set.set_type, WangSetType::Edge))
+            .find_map(|set| set.tiles.get(&id).map(|wang_id| (set, wang_id)));
+        if let Some((set, wang_id)) = wang {
+            return Some(wang_id.corners().map(|c| set.colour_name(c)));
+        }
+        let tile = self.tiles.get(&id)?;
+        if tile.terrain.iter().all(|t| t.is_none()) {
+            return None;
+        }
+        Some(tile.terrain.map(|t| t.and_then(|t| self.terrains.get(t as usize)).map(|t| t.name.as_str())))
+    }
+}
+
+impl Map {
+    /// the terrain at each corner of a tile in a layer, with the tile's flips applied, see `Tileset::corners`
+    pub fn tile_corners(&self, tile_id: u32) -> Option<Corners<'_>> {
+        let gid = Gid::new(tile_id);
+        let (ts, id) = self.tileset_for(tile_id)?;
+        let [mut tl, mut tr, mut bl, mut br] = ts.corners(id)?;
+        if gid.flip_d { std::mem::swap(&mut tr, &mut bl); }
+        if gid.flip_h {
+            std::mem::swap(&mut tl, &mut tr);
+            std::mem::swap(&mut bl, &mut br);
+        }
+        if gid.flip_v {
+            std::mem::swap(&mut tl, &mut bl);
+            std::mem::swap(&mut tr, &mut br);
+        }
+        Some([tl, tr, bl, br])
+    }
+}
@@ -0,0 +1,535 @@
+//! writes a `Map` back out as tmx that tiled can open
+
+use super::{Map, Layer, LayerKind, LayerNode, Group, Chunk, LayerData, ObjGroup, Obj, Poly, Text, ImageLayer};
+use super::{Tileset, Tile, Properties, Colour, TextHorizontalAlign, TextVerticalAlign, Orientation};
+use super::{Terrain, WangSet, WangColour};
+use super::error::{TiledError, ErrorKind};
+
+use std::fs;
+
+use quick_xml::Writer;
+use quick_xml::events::{Event, BytesStart, BytesEnd, BytesText, BytesDecl};
+
+type XmlWriter = Writer<Vec<u8>>;
+type Attribs = Vec<(&'static str, String)>;
+
+fn write(w: &mut XmlWriter, e: Event) -> Result<(), TiledError> {
+    match w.write_event(e) {
+        Ok(()) => Ok(()),
+        Err(e) => Err(ErrorKind::WriteError(e.to_string()).into()),
+    }
+}
+
+fn element(name: &str, attribs: &Attribs) -> BytesStart<'static> {
+    let mut e = BytesStart::new(name.to_string());
+    for (k, v) in attribs.iter() {
+        e.push_attribute((*k, v.as_str()));
+    }
+    e
+}
+
+fn start(w: &mut XmlWriter, name: &str, attribs: &Attribs) -> Result<(), TiledError> {
+    write(w, Event::Start(element(name, attribs)))
+}
+
+fn empty(w: &mut XmlWriter, name: &str, attribs: &Attribs) -> Result<(), TiledError> {
+    write(w, Event::Empty(element(name, attribs)))
+}
+
+fn end(w: &mut XmlWriter, name: &str) -> Result<(), TiledError> {
+    write(w, Event::End(BytesEnd::new(name.to_string())))
+}
+
+fn text(w: &mut XmlWriter, txt: &str) -> Result<(), TiledError> {
+    write(w, Event::Text(BytesText::new(txt)))
+}
+
+/// f64's display is the shortest text that parses back to the same number
+fn num(v: f64) -> String {
+    v.to_string()
+}
+
+fn flag(v: bool) -> String {
+    String::from(if v { "1" } else { "0" })
+}
+
+fn colour(c: &Colour) -> String {
+    if c.a == 255 {
+        format!("#{:02x}{:02x}{:02x}", c.r, c.g, c.b)
+    } else {
+        format!("#{:02x}{:02x}{:02x}{:02x}", c.a, c.r, c.g, c.b)
+    }
+}
+
+fn is_white(c: &Colour) -> bool {
+    c.r == 255 && c.g == 255 && c.b == 255 && c.a == 255
+}
+
+/// a path as it was written in the file, `base` is the directory it was joined onto
+fn relative<'a>(path: &'a str, base: &str) -> &'a str {
+    path.strip_prefix(base).unwrap_or(path)
+}
+
+impl Map {
+    /// the map as tmx, layers are written in the order of `layer_tree`,
+    /// followed by any that were added to the map's lists but not the tree
+    pub fn to_tmx(&self) -> Result<String, TiledError> {
+        let mut w = Writer::new_with_indent(Vec::new(), b' ', 1);
+        write(&mut w, Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+        let mut a: Attribs = vec![
+            ("version", self.metadata.version.clone()),
+            ("tiledversion", self.metadata.tiled_version.clone()),
+            ("orientation", self.orientation.name().to_string()),
+            ("renderorder", self.metadata.render_order.name().to_string()),
+            ("width", self.width.to_string()),
+            ("height", self.height.to_string()),
+            ("tilewidth", self.tile_width.to_string()),
+            ("tileheight", self.tile_height.to_string()),
+        ];
+        if let Orientation::HexagonalStaggered = self.orientation {
+            a.push(("hexsidelength", self.hex_side_length.to_string()));
+        }
+        if let Orientation::IsometricStaggered | Orientation::HexagonalStaggered = self.orientation {
+            a.push(("staggeraxis", self.stagger_axis.name().to_string()));
+            a.push(("staggerindex", self.stagger_index.name().to_string()));
+        }
+        a.push(("infinite", flag(self.infinite)));
+        a.push(("nextlayerid", self.metadata.next_layer_id.to_string()));
+        a.push(("nextobjectid", self.metadata.next_object_id.to_string()));
+        start(&mut w, "map", &a)?;
+        for ts in self.tilesets.iter() {
+            write_tileset(&mut w, ts, &self.path)?;
+        }
+        let mut written = Vec::new();
+        self.write_layer_nodes(&mut w, &self.layer_tree, &mut written)?;
+        for i in 0..self.layers.len() {
+            if !written.contains(&(LayerKind::Tile, i)) {
+                self.write_layer_of_kind(&mut w, LayerKind::Tile, i)?;
+            }
+        }
+        for i in 0..self.obj_groups.len() {
+            if !written.contains(&(LayerKind::Objects, i)) {
+                self.write_layer_of_kind(&mut w, LayerKind::Objects, i)?;
+            }
+        }
+        for i in 0..self.img_layers.len() {
+            if !written.contains(&(LayerKind::Image, i)) {
+                self.write_layer_of_kind(&mut w, LayerKind::Image, i)?;
+            }
+        }
+        end(&mut w, "map")?;
+        match String::from_utf8(w.into_inner()) {
+            Ok(mut tmx) => {
+                tmx.push('\n');
+                Ok(tmx)
+            },
+            Err(e) => Err(ErrorKind::WriteError(e.to_string()).into()),
+        }
+    }
+
+    fn write_layer_nodes(&self, w: &mut XmlWriter, nodes: &[LayerNode], written: &mut Vec<(LayerKind, usize)>) -> Result<(), TiledError> {
+        for node in nodes {
+            match node {
+                LayerNode::Layer(kind, index) => {
+                    self.write_layer_of_kind(w, *kind, *index)?;
+                    written.push((*kind, *index));
+                },
+                LayerNode::Group(g) => self.write_group(w, g, written)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn write_group(&self, w: &mut XmlWriter, g: &Group, written: &mut Vec<(LayerKind, usize)>) -> Result<(), TiledError> {
+        let a = layer_attribs(&g.info, false);
+        if is_empty(&g.props) && g.layers.is_empty() {
+            return empty(w, "group", &a);
+        }
+        start(w, "group", &a)?;
+        write_properties(w, &g.props)?;
+        self.write_layer_nodes(w, &g.layers, written)?;
+        end(w, "group")
+    }
+
+    fn write_layer_of_kind(&self, w: &mut XmlWriter, kind: LayerKind, index: usize) -> Result<(), TiledError> {
+        match kind {
+            LayerKind::Tile => write_layer(w, &self.layers[index]),
+            LayerKind::Objects => {
+                let og = &self.obj_groups[index];
+                write_obj_group(w, og, &layer_attribs(&og.info, true))
+            },
+            LayerKind::Image => write_image_layer(w, &self.img_layers[index]),
+        }
+    }
+
+    /// write the map to a tmx file, paths in the map stay relative to where it was loaded from
+    pub fn save(&self, filename: &str) -> Result<(), TiledError> {
+        match fs::write(filename, self.to_tmx()?) {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                let mut err = TiledError::new(ErrorKind::FileWriteError(e.to_string()));
+                err.file = Some(filename.to_string());
+                Err(err)
+            },
+        }
+    }
+}
+
+fn layer_attribs(info: &LayerData, obj_group: bool) -> Attribs {
+    let mut a: Attribs = vec![
+        ("id", info.id.to_string()),
+        ("name", info.name.clone()),
+    ];
+    if obj_group && !is_white(&info.colour) { a.push(("color", colour(&info.colour))); }
+    if obj_group && info.index_draw_order { a.push(("draworder", String::from("index"))); }
+    if !info.visible { a.push(("visible", flag(false))); }
+    if info.locked { a.push(("locked", flag(true))); }
+    if info.opacity != 1.0 { a.push(("opacity", num(info.opacity))); }
+    if !is_white(&info.tint) { a.push(("tintcolor", colour(&info.tint))); }
+    if info.offset.x != 0.0 { a.push(("offsetx", num(info.offset.x))); }
+    if info.offset.y != 0.0 { a.push(("offsety", num(info.offset.y))); }
+    if info.parallax.x != 1.0 { a.push(("parallaxx", num(info.parallax.x))); }
+    if info.parallax.y != 1.0 { a.push(("parallaxy", num(info.parallax.y))); }
+    a
+}
+
+fn is_empty(props: &Properties) -> bool {
+    props.booleans.is_empty() && props.integers.is_empty() && props.floats.is_empty()
+        && props.strings.is_empty() && props.colours.is_empty() && props.files.is_empty()
+        && props.objects.is_empty() && props.classes.is_empty()
+}
+
+/// the keys of a property map in a fixed order, so the same map always writes the same file
+fn sorted<V>(map: &std::collections::HashMap<String, V>) -> Vec<(&String, &V)> {
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    entries
+}
+
+fn write_properties(w: &mut XmlWriter, props: &Properties) -> Result<(), TiledError> {
+    if is_empty(props) { return Ok(()); }
+    let prop = |name: &String, t: &str, value: String| -> Attribs {
+        vec![("name", name.clone()), ("type", t.to_string()), ("value", value)]
+    };
+    start(w, "properties", &vec![])?;
+    for (k, v) in sorted(&props.booleans) {
+        empty(w, "property", &prop(k, "bool", v.to_string()))?;
+    }
+    for (k, v) in sorted(&props.integers) {
+        empty(w, "property", &prop(k, "int", v.to_string()))?;
+    }
+    for (k, v) in sorted(&props.floats) {
+        empty(w, "property", &prop(k, "float", num(*v)))?;
+    }
+    for (k, v) in sorted(&props.strings) {
+        if v.contains('\n') {
+            // multi-line strings are kept as the element's text
+            start(w, "property", &vec![("name", k.clone())])?;
+            text(w, v)?;
+            end(w, "property")?;
+        } else {
+            empty(w, "property", &vec![("name", k.clone()), ("value", v.clone())])?;
+        }
+    }
+    for (k, v) in sorted(&props.colours) {
+        // an unset colour is read in as all zeros
+        let unset = v.r == 0 && v.g == 0 && v.b == 0 && v.a == 0;
+        let c = if unset { String::new() } else { colour(v) };
+        empty(w, "property", &prop(k, "color", c))?;
+    }
+    for (k, v) in sorted(&props.files) {
+        empty(w, "property", &prop(k, "file", v.clone()))?;
+    }
+    for (k, v) in sorted(&props.objects) {
+        empty(w, "property", &prop(k, "object", v.to_string()))?;
+    }
+    for (k, v) in sorted(&props.classes) {
+        let a = vec![
+            ("name", k.clone()),
+            ("type", String::from("class")),
+            ("propertytype", v.class.clone()),
+        ];
+        if is_empty(&v.props) {
+            empty(w, "property", &a)?;
+        } else {
+            start(w, "property", &a)?;
+            write_properties(w, &v.props)?;
+            end(w, "property")?;
+        }
+    }
+    end(w, "properties")
+}
+
+fn csv(tiles: &[u32], width: i32) -> String {
+    let rows: Vec<String> = tiles.chunks(width.max(1) as usize)
+        .map(|row| row.iter().map(|t| t.to_string()).collect::<Vec<_>>().join(","))
+        .collect();
+    format!("\n{}\n", rows.join(",\n"))
+}
+
+fn write_layer(w: &mut XmlWriter, l: &Layer) -> Result<(), TiledError> {
+    let mut a = layer_attribs(&l.info, false);
+    a.push(("width", l.width.to_string()));
+    a.push(("height", l.height.to_string()));
+    start(w, "layer", &a)?;
+    write_properties(w, &l.props)?;
+    let encoding = vec![("encoding", String::from("csv"))];
+    start(w, "data", &encoding)?;
+    if l.chunks.is_empty() {
+        text(w, &csv(&l.tiles, l.width))?;
+    }
+    for c in l.chunks.iter() {
+        write_chunk(w, c)?;
+    }
+    end(w, "data")?;
+    end(w, "layer")
+}
+
+fn write_chunk(w: &mut XmlWriter, c: &Chunk) -> Result<(), TiledError> {
+    start(w, "chunk", &vec![
+        ("x", c.x.to_string()),
+        ("y", c.y.to_string()),
+        ("width", c.width.to_string()),
+        ("height", c.height.to_string()),
+    ])?;
+    text(w, &csv(&c.tiles, c.width))?;
+    end(w, "chunk")
+}
+
+/// the kinds of object an object group sorts its objects into
+enum Shape<'a> {
+    Rect,
+    Poly(&'a Poly),
+    Text(&'a Text),
+    Ellipse,
+    Point,
+}
+
+fn write_obj_group(w: &mut XmlWriter, og: &ObjGroup, attribs: &Attribs) -> Result<(), TiledError> {
+    let mut objs: Vec<(&Obj, Shape)> = Vec::new();
+    objs.extend(og.objs.iter().map(|o| (o, Shape::Rect)));
+    objs.extend(og.polys.iter().map(|p| (&p.obj, Shape::Poly(p))));
+    objs.extend(og.text.iter().map(|t| (&t.obj, Shape::Text(t))));
+    objs.extend(og.ellipse.iter().map(|o| (o, Shape::Ellipse)));
+    objs.extend(og.points.iter().map(|o| (o, Shape::Point)));
+    objs.sort_by_key(|(o, _)| o.info.id);
+
+    start(w, "objectgroup", attribs)?;
+    write_properties(w, &og.props)?;
+    for (o, shape) in objs.iter() {
+        write_obj(w, o, shape)?;
+    }
+    end(w, "objectgroup")
+}
+
+fn write_obj(w: &mut XmlWriter, o: &Obj, shape: &Shape) -> Result<(), TiledError> {
+    let mut a: Attribs = vec![("id", o.info.id.to_string())];
+    if let Some(template) = &o.template { a.push(("template", template.clone())); }
+    // defaults are written for template instances too, as they may be overriding the template
+    let all = o.template.is_some();
+    if all || !o.info.name.is_empty() { a.push(("name", o.info.name.clone())); }
+    if all || !o.info.type_name.is_empty() { a.push(("type", o.info.type_name.clone())); }
+    if o.gid != 0 { a.push(("gid", o.gid.to_string())); }
+    a.push(("x", num(o.rect.x)));
+    a.push(("y", num(o.rect.y)));
+    if all || o.rect.w != 0.0 { a.push(("width", num(o.rect.w))); }
+    if all || o.rect.h != 0.0 { a.push(("height", num(o.rect.h))); }
+    if all || o.rotation != 0.0 { a.push(("rotation", num(o.rotation))); }
+    if all || !o.info.visible { a.push(("visible", flag(o.info.visible))); }
+
+    if is_empty(&o.props) && matches!(shape, Shape::Rect) {
+        return empty(w, "object", &a);
+    }
+    start(w, "object", &a)?;
+    write_properties(w, &o.props)?;
+    match shape {
+        Shape::Rect => (),
+        Shape::Poly(p) => {
+            let points: Vec<String> = p.points.iter()
+                .map(|v| format!("{},{}", num(v.x), num(v.y)))
+                .collect();
+            let name = if p.closed { "polygon" } else { "polyline" };
+            empty(w, name, &vec![("points", points.join(" "))])?;
+        },
+        Shape::Text(t) => write_text(w, t)?,
+        Shape::Ellipse => empty(w, "ellipse", &vec![])?,
+        Shape::Point => empty(w, "point", &vec![])?,
+    }
+    end(w, "object")
+}
+
+fn write_text(w: &mut XmlWriter, t: &Text) -> Result<(), TiledError> {
+    let mut a: Attribs = vec![
+        ("fontfamily", t.font_family.clone()),
+        ("pixelsize", t.pixel_size.to_string()),
+    ];
+    if t.wrap { a.push(("wrap", flag(true))); }
+    a.push(("color", colour(&t.colour)));
+    if t.bold { a.push(("bold", flag(true))); }
+    if t.italic { a.push(("italic", flag(true))); }
+    a.push(("halign", String::from(match t.horizontal_align {
+        TextHorizontalAlign::Left => "left",
+        TextHorizontalAlign::Center => "center",
+        TextHorizontalAlign::Right => "right",
+        TextHorizontalAlign::Justify => "justify",
+    })));
+    a.push(("valign", String::from(match t.vertical_align {
+        TextVerticalAlign::Top => "top",
+        TextVerticalAlign::Center => "center",
+        TextVerticalAlign::Bottom => "bottom",
+    })));
+    start(w, "text", &a)?;
+    text(w, &t.text)?;
+    end(w, "text")
+}
+
+fn write_image_layer(w: &mut XmlWriter, img: &ImageLayer) -> Result<(), TiledError> {
+    let mut a = layer_attribs(&img.info, false);
+    if img.repeat_x { a.push(("repeatx", flag(true))); }
+    if img.repeat_y { a.push(("repeaty", flag(true))); }
+    start(w, "imagelayer", &a)?;
+    empty(w, "image", &vec![
+        ("source", img.image_path.clone()),
+        ("width", img.width.to_string()),
+        ("height", img.height.to_string()),
+    ])?;
+    write_properties(w, &img.props)?;
+    end(w, "imagelayer")
+}
+
+fn write_tileset(w: &mut XmlWriter, ts: &Tileset, map_path: &str) -> Result<(), TiledError> {
+    if let Some(source) = &ts.source {
+        return empty(w, "tileset", &vec![
+            ("firstgid", ts.first_tile_id.to_string()),
+            ("source", source.clone()),
+        ]);
+    }
+    start(w, "tileset", &vec![
+        ("firstgid", ts.first_tile_id.to_string()),
+        ("name", ts.name.clone()),
+        ("tilewidth", ts.tile_width.to_string()),
+        ("tileheight", ts.tile_height.to_string()),
+        ("spacing", ts.spacing.to_string()),
+        ("margin", ts.margin.to_string()),
+        ("tilecount", ts.tile_count.to_string()),
+        ("columns", ts.column_count.to_string()),
+    ])?;
+    if !ts.image_path.is_empty() {
+        empty(w, "image", &vec![
+            ("source", relative(&ts.image_path, map_path).to_string()),
+            ("width", ts.image_width.to_string()),
+            ("height", ts.image_height.to_string()),
+        ])?;
+    }
+    if !ts.terrains.is_empty() {
+        start(w, "terraintypes", &vec![])?;
+        for t in ts.terrains.iter() {
+            write_terrain(w, t)?;
+        }
+        end(w, "terraintypes")?;
+    }
+    let mut tiles: Vec<&Tile> = ts.tiles.values().collect();
+    tiles.sort_by_key(|t| t.id);
+    for t in tiles {
+        write_tile(w, t, map_path)?;
+    }
+    if !ts.wang_sets.is_empty() {
+        start(w, "wangsets", &vec![])?;
+        for set in ts.wang_sets.iter() {
+            write_wang_set(w, set)?;
+        }
+        end(w, "wangsets")?;
+    }
+    end(w, "tileset")
+}
+
+/// tiled writes -1 for no tile
+fn tile_index(tile: Option<u32>) -> String {
+    match tile {
+        Some(t) => t.to_string(),
+        None => String::from("-1"),
+    }
+}
+
+fn write_terrain(w: &mut XmlWriter, t: &Terrain) -> Result<(), TiledError> {
+    let a: Attribs = vec![("name", t.name.clone()), ("tile", tile_index(t.tile))];
+    if is_empty(&t.props) {
+        return empty(w, "terrain", &a);
+    }
+    start(w, "terrain", &a)?;
+    write_properties(w, &t.props)?;
+    end(w, "terrain")
+}
+
+fn write_wang_set(w: &mut XmlWriter, set: &WangSet) -> Result<(), TiledError> {
+    start(w, "wangset", &vec![
+        ("name", set.name.clone()),
+        ("type", set.set_type.name().to_string()),
+        ("tile", tile_index(set.tile)),
+    ])?;
+    write_properties(w, &set.props)?;
+    for c in set.colours.iter() {
+        write_wang_colour(w, c)?;
+    }
+    let mut tiles: Vec<_> = set.tiles.iter().collect();
+    tiles.sort_by_key(|(id, _)| **id);
+    for (id, wang_id) in tiles {
+        let colours: Vec<String> = wang_id.0.iter().map(|c| c.to_string()).collect();
+        empty(w, "wangtile", &vec![("tileid", id.to_string()), ("wangid", colours.join(","))])?;
+    }
+    end(w, "wangset")
+}
+
+fn write_wang_colour(w: &mut XmlWriter, c: &WangColour) -> Result<(), TiledError> {
+    let a: Attribs = vec![
+        ("name", c.name.clone()),
+        ("color", colour(&c.colour)),
+        ("tile", tile_index(c.tile)),
+        ("probability", num(c.probability)),
+    ];
+    if is_empty(&c.props) {
+        return empty(w, "wangcolor", &a);
+    }
+    start(w, "wangcolor", &a)?;
+    write_properties(w, &c.props)?;
+    end(w, "wangcolor")
+}
+
+fn write_tile(w: &mut XmlWriter, t: &Tile, map_path: &str) -> Result<(), TiledError> {
+    let mut a: Attribs = vec![("id", t.id.to_string())];
+    if !t.type_name.is_empty() { a.push(("type", t.type_name.clone())); }
+    if t.terrain.iter().any(|c| c.is_some()) {
+        let corners: Vec<String> = t.terrain.iter().map(|c| c.map_or(String::new(), |c| c.to_string())).collect();
+        a.push(("terrain", corners.join(",")));
+    }
+    if t.probability != 1.0 { a.push(("probability", num(t.probability))); }
+    if is_empty(&t.props) && t.image_path.is_empty() && t.collision.is_none() && t.animation.is_empty() {
+        return empty(w, "tile", &a);
+    }
+    start(w, "tile", &a)?;
+    write_properties(w, &t.props)?;
+    if !t.image_path.is_empty() {
+        empty(w, "image", &vec![
+            ("width", t.image_width.to_string()),
+            ("height", t.image_height.to_string()),
+            ("source", relative(&t.image_path, map_path).to_string()),
+        ])?;
+    }
+    if let Some(og) = &t.collision {
+        let mut a = layer_attribs(&og.info, true);
+        // tile collision groups don't have names
+        a.retain(|(k, _)| *k != "name");
+        write_obj_group(w, og, &a)?;
+    }
+    if !t.animation.is_empty() {
+        start(w, "animation", &vec![])?;
+        for f in t.animation.iter() {
+            empty(w, "frame", &vec![
+                ("tileid", f.tile_id.to_string()),
+                ("duration", f.duration.to_string()),
+            ])?;
+        }
+        end(w, "animation")?;
+    }
+    end(w, "tile")
+}
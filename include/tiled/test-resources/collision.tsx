@@ -0,0 +1,27 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.9" tiledversion="1.9.1" name="collision" tilewidth="10" tileheight="10" spacing="2" margin="5" tilecount="4" columns="2">
+ <image source="test-tileset.png" width="32" height="32"/>
+ <tile id="1" type="ground">
+  <properties>
+   <property name="solid" type="bool" value="true"/>
+  </properties>
+  <objectgroup draworder="index" id="2">
+   <object id="1" x="0" y="5" width="10" height="5"/>
+  </objectgroup>
+ </tile>
+ <tile id="2">
+  <objectgroup draworder="index" id="2">
+   <object id="1" x="0" y="0" width="10" height="10"/>
+   <object id="2" x="0" y="0">
+    <polygon points="0,0 10,10 0,10"/>
+   </object>
+  </objectgroup>
+ </tile>
+ <tile id="3" type="decoration"/>
+ <tile id="0">
+  <animation>
+   <frame tileid="0" duration="100"/>
+   <frame tileid="3" duration="250"/>
+  </animation>
+ </tile>
+</tileset>
@@ -0,0 +1,9 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.9" tiledversion="1.9.1" name="templated" tilewidth="10" tileheight="10" spacing="2" margin="5" tilecount="4" columns="2">
+ <image source="test-tileset.png" width="32" height="32"/>
+ <tile id="1">
+  <objectgroup draworder="index" id="2">
+   <object id="1" template="templates/tile.tx" x="0" y="10"/>
+  </objectgroup>
+ </tile>
+</tileset>
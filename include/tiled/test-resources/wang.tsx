@@ -0,0 +1,33 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.9" tiledversion="1.9.1" name="wang" tilewidth="10" tileheight="10" spacing="2" margin="5" tilecount="4" columns="2">
+ <image source="test-tileset.png" width="32" height="32"/>
+ <terraintypes>
+  <terrain name="grass" tile="0"/>
+  <terrain name="water" tile="-1">
+   <properties>
+    <property name="swim" type="bool" value="true"/>
+   </properties>
+  </terrain>
+ </terraintypes>
+ <tile id="0" type="ground" terrain="0,0,0,0"/>
+ <tile id="1" terrain="0,1,,1" probability="0.25"/>
+ <wangsets>
+  <wangset name="ground" type="corner" tile="-1">
+   <properties>
+    <property name="layer" value="floor"/>
+   </properties>
+   <wangcolor name="grass" color="#00ff00" tile="0" probability="1"/>
+   <wangcolor name="dirt" color="#804000" tile="-1" probability="0.5">
+    <properties>
+     <property name="slow" type="bool" value="true"/>
+    </properties>
+   </wangcolor>
+   <wangtile tileid="2" wangid="0,1,0,2,0,2,0,1"/>
+   <wangtile tileid="3" wangid="0,2,0,2,0,2,0,2"/>
+  </wangset>
+  <wangset name="paths" type="edge" tile="3">
+   <wangcolor name="path" color="#ffffff" tile="-1" probability="1"/>
+   <wangtile tileid="0" wangid="1,0,1,0,0,0,0,0"/>
+  </wangset>
+ </wangsets>
+</tileset>
@@ -1,5 +1,5 @@
 use geometry::*;
-use crate::{TextureDraw, GameObject, Colour};
+use crate::{TextureDraw, GameObject, Colour, text::TextBox};
 use std::vec::Drain;
 
 pub struct RectDraw {
@@ -10,6 +10,7 @@ pub struct RectDraw {
 pub enum CamDraw {
     Rect(RectDraw),
     Tex(TextureDraw),
+    Text(TextBox),
 }
 
 pub struct Camera {
@@ -62,19 +63,32 @@ impl Camera {
     }
     
     pub fn draw(&mut self, game_obj: &GameObject) {
-        self.draws.push(
-            CamDraw::Tex(TextureDraw::new(
-                game_obj.texture,
-                Rect::new(
-                    (game_obj.rect.x - (self.rect.x * game_obj.parallax.x)) / self.size_ratio.x,
-                    (game_obj.rect.y - (self.rect.y * game_obj.parallax.y)) / self.size_ratio.y,
-                    game_obj.rect.w / self.size_ratio.x,
-                    game_obj.rect.h / self.size_ratio.y,
-                ),
-                game_obj.tex_rect,
-                game_obj.colour,
-            )
-        ));
+        let mut tex_draw = TextureDraw::new(
+            game_obj.texture,
+            Rect::new(
+                (game_obj.rect.x - (self.rect.x * game_obj.parallax.x)) / self.size_ratio.x,
+                (game_obj.rect.y - (self.rect.y * game_obj.parallax.y)) / self.size_ratio.y,
+                game_obj.rect.w / self.size_ratio.x,
+                game_obj.rect.h / self.size_ratio.y,
+            ),
+            game_obj.tex_rect,
+            game_obj.colour,
+        );
+        tex_draw.rotation = game_obj.rotation;
+        self.draws.push(CamDraw::Tex(tex_draw));
+    }
+
+    /// draw text positioned in the world, its rect and size are scaled to the window
+    pub fn draw_text(&mut self, text: &TextBox) {
+        let mut text = text.clone();
+        text.rect = Rect::new(
+            (text.rect.x - (self.rect.x * text.parallax.x)) / self.size_ratio.x,
+            (text.rect.y - (self.rect.y * text.parallax.y)) / self.size_ratio.y,
+            text.rect.w / self.size_ratio.x,
+            text.rect.h / self.size_ratio.y,
+        );
+        text.pixel_size /= self.size_ratio.y;
+        self.draws.push(CamDraw::Text(text));
     }
 
     pub fn draw_rect(&mut self, rect: Rect, colour: Colour) {
@@ -3,14 +3,14 @@ pub use crate::{
     input::Input,
     GameObject,
     Colour,
-    TextureManager,
+    TextureLoader,
     resource::Texture,
 };
 
 pub use geometry::*;
 
 pub trait Draw {
-    fn new<'sdl, TexType>(tm: &'sdl mut TextureManager<TexType>) -> Result<Self, String>
+    fn new<TL: TextureLoader>(tm: &mut TL) -> Result<Self, String>
     where Self: Sized;
     fn update(&mut self, time: &f64, input: &Input);
     fn draw(&self, cam: &mut Camera);
@@ -0,0 +1,52 @@
+//! the list of levels the game plays through, read from a toml manifest
+
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+/// seconds before the players swap if a level doesn't set its own
+pub const SWITCH_TIME: f64 = 10.0;
+
+pub const LEVEL_MANIFEST: &str = "maps/levels.toml";
+
+#[derive(Deserialize, Clone)]
+pub struct Level {
+    /// path to the tiled map, relative to the manifest when loaded
+    pub map: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub par_time: Option<f64>,
+    #[serde(default = "default_switch_time")]
+    pub switch_time: f64,
+}
+
+fn default_switch_time() -> f64 {
+    SWITCH_TIME
+}
+
+#[derive(Deserialize)]
+struct Manifest {
+    #[serde(default, rename = "level")]
+    levels: Vec<Level>,
+}
+
+/// read the levels in a manifest, with their map paths made relative to the working directory
+pub fn load_levels(manifest_path: &str) -> Result<Vec<Level>, String> {
+    let text = fs::read_to_string(manifest_path)
+        .map_err(|e| format!("failed to read level manifest {}: {}", manifest_path, e))?;
+    let manifest: Manifest = toml::from_str(&text)
+        .map_err(|e| format!("failed to parse level manifest {}: {}", manifest_path, e))?;
+    if manifest.levels.is_empty() {
+        return Err(format!("level manifest {} has no levels", manifest_path));
+    }
+    let dir = Path::new(manifest_path).parent().unwrap_or(Path::new(""));
+    Ok(manifest.levels
+       .into_iter()
+       .map(|mut l| {
+           l.map = dir.join(&l.map).to_string_lossy().to_string();
+           l
+       })
+       .collect())
+}
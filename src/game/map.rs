@@ -1,16 +1,18 @@
 use std::path::Path;
 
 use tiled::{self, Properties};
-use crate::{TextDraw, GameObject, resource, camera::Camera, Colour};
-use crate::{TextureManager, resource::Texture};
-use sdl2::render::Canvas;
-use sdl2::video::Window;
-use geometry::Rect;
+use crate::{GameObject, resource, camera::Camera, Colour};
+use crate::text::{TextBox, HorizontalAlign, VerticalAlign};
+use crate::{TextureLoader, resource::Texture};
+use super::physics::{Shape, Slope, ellipse_points};
+use geometry::{Rect, Vec2};
 
 #[derive(Clone)]
 struct Tile {
     pub tex : Texture,
     pub rect: Rect,
+    /// the tile id and seconds to show each frame for, empty if not animated
+    pub animation: Vec<(usize, f64)>,
 }
 
 impl Tile {
@@ -18,6 +20,7 @@ impl Tile {
         Tile {
             tex: Texture{ id: 0, width: 0, height: 0},
             rect: Rect::blank(),
+            animation: Vec::new(),
         }
     }
 }
@@ -43,41 +46,324 @@ fn load_tileset(tiles: &mut Vec<Tile>, ts: &tiled::Tileset, tex: resource::Textu
     Ok(())
 }
 
+/// load each tile of an image collection tileset as its own texture
+fn load_image_collection<TL: TextureLoader>(tiles: &mut [Tile], ts: &tiled::Tileset, tex_manager: &mut TL, texture_paths: &mut Vec<String>) -> Result<(), String> {
+    for (id, t) in ts.tiles.iter() {
+        if t.image_path.is_empty() { continue; }
+        let tex = tex_manager.load(&t.image_path)?;
+        texture_paths.push(t.image_path.clone());
+        let tile = &mut tiles[(ts.first_tile_id + id) as usize];
+        tile.tex = tex;
+        tile.rect = Rect::new(0.0, 0.0, t.image_width as f64, t.image_height as f64);
+    }
+    Ok(())
+}
+
+fn load_animations(tiles: &mut [Tile], ts: &tiled::Tileset) {
+    for (id, t) in ts.tiles.iter() {
+        tiles[(ts.first_tile_id + id) as usize].animation = t.animation.iter()
+            .map(|f| ((ts.first_tile_id + f.tile_id) as usize, f.duration as f64 / 1000.0))
+            .collect();
+    }
+}
+
 struct Layer {
     tile_draws: Vec<GameObject>,
+    /// the index into `tile_draws` and raw tile id of each animated tile
+    animated: Vec<(usize, u32)>,
 }
 
 impl Layer {
-    fn new(l: &tiled::Layer, tiles: &Vec<Tile>) -> Layer {
-        let mut layer = Layer { tile_draws: Vec::new() };
-        for y in 0..l.height {
-            for x in 0..l.width {
-                let tile_id = l.tiles[(y * l.width + x) as usize] as usize;
-                if tile_id == 0 { continue; }
-                let tile = &tiles[tile_id];
-                layer.tile_draws.push(
-                    GameObject::new(
-                        tile.tex,
-                        Rect::new(
-                            l.info.offset.x + (x as f64 * tile.rect.w),
-                            l.info.offset.y + (y as f64 * tile.rect.h),
-                            tile.rect.w,
-                            tile.rect.h,
-                        ),
-                        tile.rect,
-                        l.info.parallax,
-                        Colour::new(
-                            l.info.tint.r as u8,
-                            l.info.tint.g as u8,
-                            l.info.tint.b as u8,
-                            (l.info.opacity * 255.0) as u8,
-                        )
-                    )
-                );
-            }
+    /// `info` is the layer's settings with those of any groups it is in applied
+    fn new(l: &tiled::Layer, info: &tiled::LayerData, tiles: &[Tile], map: &tiled::Map) -> Layer {
+        let mut layer = Layer { tile_draws: Vec::new(), animated: Vec::new() };
+        // tiles further down are drawn later, so in isometric and hexagonal maps they overlap the ones behind them
+        let mut cells: Vec<(Rect, u32)> = l.cells().into_iter()
+            .map(|(x, y, tile_id)| (map.tile_rect(x, y), tile_id))
+            .collect();
+        cells.sort_by(|a, b| a.0.y.total_cmp(&b.0.y));
+        for (cell, tile_id) in cells {
+            layer.add_tile(info, cell, tile_id, tiles);
+        }
+        layer
+    }
+
+    /// add a draw for a tile, from the bottom left of the map cell it is in
+    fn add_tile(&mut self, info: &tiled::LayerData, cell: Rect, tile_id: u32, tiles: &[Tile]) {
+        let gid = tiled::Gid::new(tile_id);
+        let tile = &tiles[gid.id as usize];
+        let mut draw = GameObject::new(
+                tile.tex,
+                Rect::new(
+                    info.offset.x + cell.x,
+                    info.offset.y + cell.y + cell.h - tile.rect.h,
+                    tile.rect.w,
+                    tile.rect.h,
+                ),
+                tile.rect,
+                info.parallax,
+                layer_colour(info),
+            );
+        set_flips(&mut draw, gid);
+        self.push(draw, tile_id, tile);
+    }
+
+    /// the draws for the visible tile objects in an object group, in the order they were placed,
+    /// or from the top of the map down if the group isn't drawn in index order
+    fn new_from_objects(og: &tiled::ObjGroup, info: &tiled::LayerData, tiles: &[Tile], map: &tiled::Map) -> Layer {
+        let mut layer = Layer { tile_draws: Vec::new(), animated: Vec::new() };
+        let mut objs: Vec<&tiled::Obj> = og.objs.iter()
+            .filter(|o| o.info.visible && o.tile().is_some())
+            .collect();
+        objs.sort_by_key(|o| o.info.id);
+        if !info.index_draw_order {
+            let y = |o: &tiled::Obj| map.object_to_world(o.rect.top_left()).y;
+            objs.sort_by(|a, b| y(a).total_cmp(&y(b)));
+        }
+        for o in objs {
+            layer.add_sprite(info, o, tiles, map);
         }
         layer
     }
+
+    /// add a draw for a tile object, stretched to the object's size and rotated around
+    /// its bottom left corner as tiled does, or its bottom centre in isometric maps
+    fn add_sprite(&mut self, info: &tiled::LayerData, o: &tiled::Obj, tiles: &[Tile], map: &tiled::Map) {
+        let gid = tiled::Gid::new(o.gid);
+        let tile = &tiles[gid.id as usize];
+        let (w, h) = if o.rect.w > 0.0 && o.rect.h > 0.0 {
+            (o.rect.w, o.rect.h)
+        } else {
+            (tile.rect.w, tile.rect.h)
+        };
+        let pos = map.object_to_world(o.rect.top_left());
+        let anchor = match map.orientation {
+            tiled::Orientation::Isometric => w / 2.0,
+            _ => 0.0,
+        };
+        // draws rotate around their centre, so move the centre to where turning around the anchor puts it
+        let (sin, cos) = o.rotation.to_radians().sin_cos();
+        let (dx, dy) = (w / 2.0 - anchor, -h / 2.0);
+        let centre = Vec2::new(
+            pos.x + dx * cos - dy * sin,
+            pos.y + dx * sin + dy * cos,
+        );
+        let mut draw = GameObject::new(
+                tile.tex,
+                Rect::new(
+                    info.offset.x + centre.x - w / 2.0,
+                    info.offset.y + centre.y - h / 2.0,
+                    w,
+                    h,
+                ),
+                tile.rect,
+                info.parallax,
+                layer_colour(info),
+            );
+        set_flips(&mut draw, gid);
+        draw.rotation += o.rotation;
+        self.push(draw, o.gid, tile);
+    }
+
+    fn push(&mut self, draw: GameObject, tile_id: u32, tile: &Tile) {
+        if !tile.animation.is_empty() {
+            self.animated.push((self.tile_draws.len(), tile_id));
+        }
+        self.tile_draws.push(draw);
+    }
+
+    /// show the frame of each animated tile for `time` seconds since the map loaded
+    fn animate(&mut self, time: f64, tiles: &[Tile]) {
+        for (i, tile_id) in self.animated.iter() {
+            let gid = tiled::Gid::new(*tile_id);
+            let frames = &tiles[gid.id as usize].animation;
+            let length: f64 = frames.iter().map(|(_, d)| d).sum();
+            let mut t = if length > 0.0 { time % length } else { 0.0 };
+            let mut frame = frames[0].0;
+            for (id, duration) in frames.iter() {
+                frame = *id;
+                if t < *duration { break; }
+                t -= duration;
+            }
+            let draw = &mut self.tile_draws[*i];
+            // tile objects can be stretched, so keep the draw's scale
+            let scale_x = draw.rect.w / draw.tex_rect.w.abs();
+            let scale_y = draw.rect.h / draw.tex_rect.h.abs();
+            let rotation = draw.rotation;
+            draw.texture = tiles[frame].tex;
+            draw.tex_rect = tiles[frame].rect;
+            // frames in an image collection can differ in size
+            let h = draw.tex_rect.h * scale_y;
+            draw.rect.y += draw.rect.h - h;
+            draw.rect.w = draw.tex_rect.w * scale_x;
+            draw.rect.h = h;
+            set_flips(draw, gid);
+            draw.rotation = rotation;
+        }
+    }
+}
+
+/// an image layer, repeated to fill the view along the axes tiled repeats it on
+struct ImageLayer {
+    draw: GameObject,
+    repeat_x: bool,
+    repeat_y: bool,
+}
+
+impl ImageLayer {
+    fn draw(&self, cam: &mut Camera) {
+        let view = cam.get_view_size();
+        // the area of the layer the camera sees once parallax is applied
+        let seen = cam.get_offset();
+        let xs = repeat_positions(self.draw.rect.x, self.draw.rect.w, seen.x * self.draw.parallax.x, view.x, self.repeat_x);
+        let ys = repeat_positions(self.draw.rect.y, self.draw.rect.h, seen.y * self.draw.parallax.y, view.y, self.repeat_y);
+        let mut draw = self.draw;
+        for y in ys.iter() {
+            for x in xs.iter() {
+                draw.rect.x = *x;
+                draw.rect.y = *y;
+                cam.draw(&draw);
+            }
+        }
+    }
+}
+
+/// where copies of an image at `pos` of length `size` go to cover
+/// `start` to `start + length`, or just `pos` if it doesn't repeat
+fn repeat_positions(pos: f64, size: f64, start: f64, length: f64, repeat: bool) -> Vec<f64> {
+    if !repeat || size <= 0.0 {
+        return vec![pos];
+    }
+    let mut p = pos + ((start - pos) / size).floor() * size;
+    let mut positions = Vec::new();
+    while p < start + length {
+        positions.push(p);
+        p += size;
+    }
+    positions
+}
+
+enum MapLayer {
+    Tiles(Layer),
+    Image(ImageLayer),
+    Text(Vec<TextBox>),
+}
+
+/// a tiled text object as world space text, with its layer's settings applied
+fn text_box(t: &tiled::Text, info: &tiled::LayerData, map: &tiled::Map) -> TextBox {
+    let mut text = TextBox::new(&t.text, t.obj.rect, t.pixel_size as f64);
+    // text isn't skewed in isometric maps, only moved to where its corner is
+    text.rect.set_pos(&(map.object_to_world(t.obj.rect.top_left()) + info.offset));
+    text.font_family = t.font_family.clone();
+    text.parallax = info.parallax;
+    text.colour = Colour::new(
+        (t.colour.r * info.tint.r / 255) as u8,
+        (t.colour.g * info.tint.g / 255) as u8,
+        (t.colour.b * info.tint.b / 255) as u8,
+        (t.colour.a as f64 * info.opacity) as u8,
+    );
+    text.wrap = t.wrap;
+    text.bold = t.bold;
+    text.italic = t.italic;
+    text.horizontal_align = match t.horizontal_align {
+        tiled::TextHorizontalAlign::Left => HorizontalAlign::Left,
+        tiled::TextHorizontalAlign::Center => HorizontalAlign::Centre,
+        tiled::TextHorizontalAlign::Right => HorizontalAlign::Right,
+        tiled::TextHorizontalAlign::Justify => HorizontalAlign::Justify,
+    };
+    text.vertical_align = match t.vertical_align {
+        tiled::TextVerticalAlign::Top => VerticalAlign::Top,
+        tiled::TextVerticalAlign::Center => VerticalAlign::Centre,
+        tiled::TextVerticalAlign::Bottom => VerticalAlign::Bottom,
+    };
+    text
+}
+
+/// the tint and opacity of a layer as a draw colour
+fn layer_colour(info: &tiled::LayerData) -> Colour {
+    Colour::new(
+        info.tint.r as u8,
+        info.tint.g as u8,
+        info.tint.b as u8,
+        (info.opacity * 255.0) as u8,
+    )
+}
+
+/// apply tiled's flip flags to a draw, a diagonal flip is a 90 degree
+/// rotation with the horizontal and vertical flips swapped around
+fn set_flips(draw: &mut GameObject, gid: tiled::Gid) {
+    let (flip_h, flip_v) = if gid.flip_d {
+        draw.rotation = 90.0;
+        (gid.flip_v, !gid.flip_h)
+    } else {
+        (gid.flip_h, gid.flip_v)
+    };
+    if flip_h { draw.tex_rect.w *= -1.0; }
+    if flip_v { draw.tex_rect.h *= -1.0; }
+}
+
+/// points turned clockwise by `degrees` around `origin`
+pub fn rotated(points: Vec<Vec2>, origin: Vec2, degrees: f64) -> Vec<Vec2> {
+    if degrees == 0.0 { return points; }
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    points.into_iter().map(|p| {
+        let d = p - origin;
+        Vec2::new(origin.x + d.x * cos - d.y * sin, origin.y + d.x * sin + d.y * cos)
+    }).collect()
+}
+
+/// a polygon or polyline's points in the space its group is in, turned around its position as tiled does
+pub fn poly_points(p: &tiled::Poly) -> Vec<Vec2> {
+    let origin = p.obj.rect.top_left();
+    rotated(p.points.iter().map(|pt| *pt + origin).collect(), origin, p.obj.rotation)
+}
+
+/// the outlines of an object group's polygons and ellipses, with their objects
+pub fn shape_outlines(og: &tiled::ObjGroup) -> Vec<(&tiled::Obj, Vec<Vec2>)> {
+    og.polys.iter()
+        .filter(|p| p.closed)
+        .map(|p| (&p.obj, poly_points(p)))
+        .chain(og.ellipse.iter().map(|e| (e, rotated(ellipse_points(e.rect), e.rect.top_left(), e.rotation))))
+        .collect()
+}
+
+/// a rect's corners, clockwise from the top left
+pub fn corners(r: Rect) -> Vec<Vec2> {
+    vec![
+        r.top_left(),
+        Vec2::new(r.x + r.w, r.y),
+        Vec2::new(r.x + r.w, r.y + r.h),
+        Vec2::new(r.x, r.y + r.h),
+    ]
+}
+
+/// the solid parts of a map's tiles
+pub struct TileCollision {
+    /// unturned boxes, with runs of them side by side joined
+    pub rects: Vec<Rect>,
+    /// polygons, ellipses and turned boxes, with the rects around them
+    pub shapes: Vec<(Rect, Shape)>,
+    /// polylines, which players walk along like other solid ones
+    pub slopes: Vec<Slope>,
+}
+
+/// join rects that sit side by side in a row so
+/// a run of solid tiles becomes a single obstacle
+fn merge_rows(mut rects: Vec<Rect>) -> Vec<Rect> {
+    rects.sort_by(|a, b| a.y.total_cmp(&b.y)
+                  .then(a.h.total_cmp(&b.h))
+                  .then(a.x.total_cmp(&b.x)));
+    let mut merged: Vec<Rect> = Vec::new();
+    for r in rects {
+        if let Some(last) = merged.last_mut() {
+            if last.y == r.y && last.h == r.h && last.x + last.w == r.x {
+                last.w += r.w;
+                continue;
+            }
+        }
+        merged.push(r);
+    }
+    merged
 }
 
 pub fn check_bool(k: &str, props: &Properties) -> bool {
@@ -95,45 +381,194 @@ pub fn get_int(k: &str, props: &Properties, default: i64) -> i64{
 pub struct Map {
     pub tiled_map: tiled::Map,
     tiles : Vec<Tile>,
-    layers : Vec<Layer>,
+    layers : Vec<MapLayer>,
+    texture_paths: Vec<String>,
+    time: f64,
 }
 
 impl Map {
-    pub fn new<'sdl, TexType>(filename: &str, tex_manager : &'sdl mut TextureManager<TexType>) -> Result<Self, String> {
+    pub fn new<TL: TextureLoader>(filename: &str, tex_manager : &mut TL) -> Result<Self, String> {
+        let tiled_map = tiled::Map::new(filename).map_err(|e| format!("failed to load map {}", e))?;
+        Self::new_from_tiled(tiled_map, tex_manager)
+    }
+
+    /// load the textures for an already parsed map
+    pub fn new_from_tiled<TL: TextureLoader>(tiled_map: tiled::Map, tex_manager : &mut TL) -> Result<Self, String> {
         let mut map = Self {
-            tiled_map: tiled::Map::new(filename).unwrap(),
+            tiled_map,
             tiles: Vec::new(),
             layers: Vec::new(),
+            texture_paths: Vec::new(),
+            time: 0.0,
         };
 
         map.load_tilesets(tex_manager)?;
-        map.set_map_draws();
+        map.set_map_draws(tex_manager)?;
         
         Ok(map)
     }
 
+    /// give back the textures this map loaded, so ones no other map uses are unloaded
+    pub fn release<TL: TextureLoader>(self, tex_manager : &mut TL) {
+        for path in self.texture_paths.iter() {
+            tex_manager.unload(path);
+        }
+    }
+
+    /// the tileset and image files this map was built from
+    pub fn source_files(&self) -> Vec<String> {
+        let mut files: Vec<String> = self.tiled_map.tilesets.iter()
+            .filter_map(|ts| ts.source.as_ref())
+            .map(|source| tiled::join_path(&self.tiled_map.path, source))
+            .collect();
+        files.extend(self.texture_paths.iter().cloned());
+        files
+    }
+
+    /// advance animated tiles and tile objects
+    pub fn update(&mut self, time: &f64) {
+        self.time += time;
+        for l in self.layers.iter_mut() {
+            if let MapLayer::Tiles(l) = l {
+                l.animate(self.time, &self.tiles);
+            }
+        }
+    }
+
     pub fn draw(&self, cam: &mut Camera) {
         for l in self.layers.iter() {
-            for t in l.tile_draws.iter() {
-                cam.draw(t);
+            match l {
+                MapLayer::Tiles(l) => for t in l.tile_draws.iter() {
+                    cam.draw(t);
+                },
+                MapLayer::Image(img) => img.draw(cam),
+                MapLayer::Text(texts) => for t in texts.iter() {
+                    cam.draw_text(t);
+                },
+            }
+        }
+    }
+
+    /// the collision shapes tiles were given in their tileset, placed and flipped like the tiles,
+    /// hidden layers are skipped, as are layers with parallax since they don't line up with the world
+    pub fn tile_collision(&self) -> TileCollision {
+        let mut collision = TileCollision { rects: Vec::new(), shapes: Vec::new(), slopes: Vec::new() };
+        for d in self.tiled_map.draw_order() {
+            if !d.info.visible { continue; }
+            if d.kind != tiled::LayerKind::Tile { continue; }
+            if d.info.parallax.x != 1.0 || d.info.parallax.y != 1.0 { continue; }
+            for (x, y, tile_id) in self.tiled_map.layers[d.index].cells() {
+                self.add_tile_collision(&mut collision, &d.info, x, y, tile_id);
             }
         }
+        collision.rects = merge_rows(collision.rects);
+        collision
     }
 
-    fn load_tilesets<'sdl, TexType>(&mut self, tex_manager : &'sdl mut TextureManager<TexType>) -> Result<(), String> {
+    fn add_tile_collision(&self, collision: &mut TileCollision, info: &tiled::LayerData, tile_x: i32, tile_y: i32, tile_id: u32) {
+        let shapes = match self.tiled_map.tile_data(tile_id).and_then(|t| t.collision.as_ref()) {
+            Some(shapes) => shapes,
+            None => return,
+        };
+        let gid = tiled::Gid::new(tile_id);
+        let size = self.tiles[gid.id as usize].rect;
+        let cell = self.tiled_map.tile_rect(tile_x, tile_y);
+        let x = info.offset.x + cell.x;
+        let y = info.offset.y + cell.y + cell.h - size.h;
+        // from the tile's own space to the map's, flipped as the tile is
+        let place = |points: Vec<Vec2>| -> Vec<Vec2> {
+            points.into_iter().map(|mut p| {
+                if gid.flip_d { p = Vec2::new(p.y, p.x); }
+                if gid.flip_h { p.x = size.w - p.x; }
+                if gid.flip_v { p.y = size.h - p.y; }
+                Vec2::new(x + p.x, y + p.y)
+            }).collect()
+        };
+        for o in shapes.objs.iter() {
+            let corners = corners(o.bounds());
+            if o.rotation == 0.0 {
+                let corners = place(corners);
+                collision.rects.push(Rect::new_from_vec2s(&corners[0], &corners[2]));
+            } else {
+                collision.shapes.push(Shape::new(&place(rotated(corners, o.rect.top_left(), o.rotation))));
+            }
+        }
+        for (_, points) in shape_outlines(shapes) {
+            collision.shapes.push(Shape::new(&place(points)));
+        }
+        for p in shapes.polys.iter().filter(|p| !p.closed) {
+            collision.slopes.push(Slope::new(place(poly_points(p))));
+        }
+    }
+
+    fn load_tilesets<TL: TextureLoader>(&mut self, tex_manager : &mut TL) -> Result<(), String> {
         self.tiles.resize(self.tiled_map.total_tiles as usize, Tile::new());
         // blank tile
         self.tiles[0].rect.w = self.tiled_map.tile_width as f64;
         self.tiles[0].rect.h = self.tiled_map.tile_height as f64;
         for ts in self.tiled_map.tilesets.iter() {
-            load_tileset(&mut self.tiles, ts, tex_manager.load(&ts.image_path)?)?;
+            if ts.image_path.is_empty() {
+                load_image_collection(&mut self.tiles, ts, tex_manager, &mut self.texture_paths)?;
+            } else {
+                let tex = tex_manager.load(&ts.image_path)?;
+                self.texture_paths.push(ts.image_path.clone());
+                load_tileset(&mut self.tiles, ts, tex)?;
+            }
+            load_animations(&mut self.tiles, ts);
         }
         Ok(())
     }
 
-    fn set_map_draws(&mut self) {
-        for l in self.tiled_map.layers.iter() {
-            self.layers.push(Layer::new(&l, &self.tiles));
+    /// build the draws for each layer in the order tiled draws them,
+    /// so layers inside groups are drawn between their neighbours
+    fn set_map_draws<TL: TextureLoader>(&mut self, tex_manager : &mut TL) -> Result<(), String> {
+        for d in self.tiled_map.draw_order() {
+            if !d.info.visible { continue; }
+            match d.kind {
+                tiled::LayerKind::Tile => {
+                    let l = &self.tiled_map.layers[d.index];
+                    self.layers.push(MapLayer::Tiles(Layer::new(l, &d.info, &self.tiles, &self.tiled_map)));
+                },
+                tiled::LayerKind::Image => {
+                    let img = &self.tiled_map.img_layers[d.index];
+                    if img.image_path.is_empty() { continue; }
+                    // image layer paths are relative to the map file
+                    let path = tiled::join_path(&self.tiled_map.path, &img.image_path);
+                    let tex = tex_manager.load(&path)?;
+                    self.texture_paths.push(path);
+                    let mut draw = GameObject::new_from_tex(tex);
+                    if img.width != 0 && img.height != 0 {
+                        draw.rect.w = img.width as f64;
+                        draw.rect.h = img.height as f64;
+                    }
+                    draw.rect.x = d.info.offset.x;
+                    draw.rect.y = d.info.offset.y;
+                    draw.parallax = d.info.parallax;
+                    draw.colour = layer_colour(&d.info);
+                    self.layers.push(MapLayer::Image(ImageLayer {
+                        draw,
+                        repeat_x: img.repeat_x,
+                        repeat_y: img.repeat_y,
+                    }));
+                },
+                tiled::LayerKind::Objects => {
+                    let og = &self.tiled_map.obj_groups[d.index];
+                    let sprites = Layer::new_from_objects(og, &d.info, &self.tiles, &self.tiled_map);
+                    if !sprites.tile_draws.is_empty() {
+                        self.layers.push(MapLayer::Tiles(sprites));
+                    }
+                    let mut texts: Vec<TextBox> = Vec::new();
+                    for t in og.text.iter().filter(|t| t.obj.info.visible) {
+                        let mut text = text_box(t, &d.info, &self.tiled_map);
+                        text.font = Some(tex_manager.load_font(&text.font_family)?);
+                        texts.push(text);
+                    }
+                    if !texts.is_empty() {
+                        self.layers.push(MapLayer::Text(texts));
+                    }
+                },
+            }
         }
+        Ok(())
     }
 }
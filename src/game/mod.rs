@@ -1,4 +1,5 @@
 mod map;
+mod levels;
 mod helper;
 mod player;
 mod world;
@@ -9,10 +10,16 @@ use player::Player;
 use physics::*;
 use world::*;
 use map::*;
+pub use levels::{Level, LEVEL_MANIFEST};
 
 use std::collections::HashMap;
+use std::path::Path;
+use std::thread::{self, JoinHandle};
 
-const SWITCH_TIME: f64 = 10.0;
+use crate::hot_reload::same_file;
+
+const QUICK_TEXTURE: &str = "textures/quick.png";
+const HEAVY_TEXTURE: &str = "textures/heavy.png";
 
 #[derive(PartialEq, Hash, Eq, Clone, Copy)]
 pub enum Players {
@@ -22,27 +29,39 @@ pub enum Players {
 
 pub struct Game {
     player: HashMap<Players, Player>,
-    maps: Vec<Map>,
+    map: Map,
+    levels: Vec<Level>,
     m: usize,
+    /// level to switch to and if the camera should snap to it rather than pan
+    pending_level: Option<(usize, bool)>,
+    prefetch: bool,
+    prefetched: Option<(usize, JoinHandle<Result<tiled::Map, tiled::error::TiledError>>)>,
     p : Players,
     finished_count: u8,
     player_spawn: Vec2,
     objects: Vec<Box<dyn Phys>>,
     nested: Vec<world::Nested>,
+    slopes: Vec<Slope>,
     prev_input: Input,
     map_loaded: bool,
     cam_returned: bool,
     switch_time: f64,
+    level_time: f64,
     level_complete: bool,
 }
 
 impl Game {
-    pub fn new<'sdl, TexType>(tm: &'sdl mut TextureManager<TexType>) -> Result<Game, String> {
+    pub fn new<TL: TextureLoader>(tm: &mut TL) -> Result<Game, String> {
+        Self::new_from_manifest(LEVEL_MANIFEST, tm)
+    }
+
+    /// load the levels listed in a toml manifest, see `maps/levels.toml`
+    pub fn new_from_manifest<TL: TextureLoader>(manifest: &str, tm: &mut TL) -> Result<Game, String> {
         let mut player = HashMap::new();
         player.insert(
             Players::Quick,
             Player::new(
-                tm.load("textures/quick.png")?,
+                tm.load(QUICK_TEXTURE)?,
                 Vec2::new(500.0, 550.0),
                 -240.0, 3.0,
                 Vec2::new(150.0, 800.0),
@@ -51,7 +70,7 @@ impl Game {
         );
         player.insert(
             Players::Heavy,
-            Player::new(tm.load("textures/heavy.png")?,
+            Player::new(tm.load(HEAVY_TEXTURE)?,
                         Vec2::new(400.0, 500.0),
                         -160.0, 2.0,
                         Vec2::new(100.0, 800.0),
@@ -59,28 +78,33 @@ impl Game {
             )
         );
 
-        let mut maps = Vec::new();
-        for i in 0..6 {
-            maps.push(Map::new(&("maps/".to_owned() + &i.to_string() + ".tmx"), tm)?);
-        }
+        let levels = levels::load_levels(manifest)?;
+        let map = Map::new(&levels[0].map, tm)?;
         
         let mut g = Game {
             player,
-            maps,
+            map,
+            levels,
+            pending_level: None,
+            prefetch: true,
+            prefetched: None,
             objects : Vec::new(),
             p: Players::Heavy,
             m: 0,
             prev_input: Input::new(),
             nested : Vec::new(),
+            slopes: Vec::new(),
             player_spawn: Vec2::new(0.0, 0.0),
             finished_count: 0,
             map_loaded: false,
             cam_returned: true,
             switch_time: 0.0,
+            level_time: 0.0,
             level_complete: false,
         };
         g.load_map();
         g.cam_returned = true;
+        g.prefetch_level(1);
         Ok(g)
     }
 
@@ -95,6 +119,117 @@ impl Game {
     pub fn game_complete(&self) -> bool {
         self.level_complete
     }
+
+    pub fn level(&self) -> usize {
+        self.m
+    }
+
+    /// the manifest entry for the current level
+    pub fn level_info(&self) -> &Level {
+        &self.levels[self.m]
+    }
+
+    pub fn level_count(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// seconds spent on the current level since it was last (re)started
+    pub fn level_time(&self) -> f64 {
+        self.level_time
+    }
+
+    /// restart the game from the start of level `m`
+    pub fn goto_level<TL: TextureLoader>(&mut self, m: usize, tm: &mut TL) -> Result<(), String> {
+        if m >= self.levels.len() {
+            return Err(format!("there is no level {}, only {} levels", m, self.levels.len()));
+        }
+        self.pending_level = None;
+        self.switch_level(m, false, tm)?;
+        self.level_complete = false;
+        self.prev_input = Input::new();
+        self.cam_returned = true;
+        Ok(())
+    }
+
+    /// parse the next level on a background thread while the current one is played
+    pub fn set_prefetch(&mut self, prefetch: bool) {
+        self.prefetch = prefetch;
+        if !prefetch {
+            self.prefetched = None;
+        }
+    }
+
+    /// load a level that `update` asked to move to, call before each `update`
+    pub fn stream_levels<TL: TextureLoader>(&mut self, tm: &mut TL) -> Result<(), String> {
+        if let Some((m, snap_camera)) = self.pending_level.take() {
+            self.switch_level(m, snap_camera, tm)?;
+        }
+        Ok(())
+    }
+
+    /// the map, tileset and texture files the current level was loaded from
+    pub fn watched_files(&self) -> Vec<String> {
+        let mut files = vec![
+            self.levels[self.m].map.clone(),
+            QUICK_TEXTURE.to_string(),
+            HEAVY_TEXTURE.to_string(),
+        ];
+        files.extend(self.map.source_files());
+        files
+    }
+
+    /// what the parser skipped in the current level's map, each warning is only handed out once
+    pub fn take_warnings(&mut self) -> Vec<tiled::error::Warning> {
+        std::mem::take(&mut self.map.tiled_map.warnings)
+    }
+
+    /// reload changed textures in place and restart the level if a file its map was built from changed,
+    /// if the edited map fails to load the old one is kept and the error returned
+    pub fn hot_reload<TL: TextureLoader>(&mut self, changed: &[String], tm: &mut TL) -> Result<(), String> {
+        let watched = self.watched_files();
+        let mut restart = false;
+        let mut refetch = false;
+        for path in changed {
+            match Path::new(path).extension().and_then(|e| e.to_str()).unwrap_or("") {
+                "tmx" | "tmj" => if same_file(path, &self.levels[self.m].map) {
+                    restart = true;
+                } else {
+                    refetch = true;
+                },
+                "tsx" | "tsj" => {
+                    restart |= watched.iter().any(|w| same_file(path, w));
+                    refetch = true;
+                },
+                // which maps use a template isn't kept, so assume they all do
+                "tx" | "tj" => {
+                    restart = true;
+                    refetch = true;
+                },
+                _ => for w in watched.iter().filter(|w| same_file(path, w)) {
+                    tm.reload(w)?;
+                },
+            }
+        }
+        if restart {
+            self.switch_level(self.m, true, tm)?;
+        } else if refetch {
+            self.prefetch_level(self.m + 1);
+        }
+        Ok(())
+    }
+
+    pub fn current_player(&self) -> Players {
+        self.p
+    }
+
+    pub fn player_rect(&self, p: Players) -> Rect {
+        self.player[&p].pr_im().rect
+    }
+
+    /// the rects around the level's obstacles, in the order they were loaded
+    pub fn object_rects(&self) -> Vec<Rect> {
+        self.objects.iter().map(|o| o.pr_im().rect).collect()
+    }
     
     pub fn update(&mut self, time: &f64, input: &Input) {
         if input.debug_1 && !self.prev_input.debug_1{
@@ -104,18 +239,24 @@ impl Game {
             self.next_lvl();
         }
         if input.debug_3 && !self.prev_input.debug_3{
-            self.m = if self.m == 0 { 0 } else { self.m - 1}; 
-            self.load_map();
+            self.pending_level = Some((if self.m == 0 { 0 } else { self.m - 1}, false));
+        }
+        if self.pending_level.is_some() {
+            self.prev_input = *input;
+            return;
         }
 
+        self.map.update(time);
+
         if !self.cam_returned { return; }
 
         if input.restart && !self.prev_input.restart {
             self.load_map();
         }
 
+        self.level_time += time;
         self.switch_time += time;
-        if self.switch_time > SWITCH_TIME {
+        if self.switch_time > self.levels[self.m].switch_time {
             self.switch_time = 0.0;
             self.swap_player();
         }
@@ -124,27 +265,42 @@ impl Game {
         phys_update(
             &mut self.objects, time,
             &mut self.player.get_mut(&self.p).unwrap(),
-            &mut self.nested
+            &mut self.nested,
+            &self.slopes,
         );
         self.check_destroyed();
         self.prev_input = *input;
         //println!("phys: {}     nest: {}", self.objects.len(), self.nested.len()); 
     }
 
-    pub fn draw(&mut self, cam: &mut Camera) {
+    /// move the camera towards the current player, call once per physics step before `update`
+    pub fn update_camera(&mut self, cam: &mut Camera) {
         let p = self.player[&self.p].pr_im().get_pixel_correct_rect().centre();
         if self.map_loaded {
-            cam.target_centre_pos(p, self.maps[self.m].tiled_map.rect);
+            cam.target_centre_pos(p, self.map.tiled_map.rect);
             self.map_loaded = false;
             self.cam_returned = false;
         } else {
             if cam.done() || self.cam_returned {
-                cam.centre_on_pos(p, self.maps[self.m].tiled_map.rect);
+                cam.centre_on_pos(p, self.map.tiled_map.rect);
                 self.cam_returned = true;
             }
         }
-        
-        self.maps[self.m].draw(cam);
+    }
+
+    /// `alpha` is how far the frame is between the last physics step and the next
+    pub fn draw(&mut self, cam: &mut Camera, alpha: f64) {
+        for p in self.player.values_mut() {
+            p.go.rect = p.pr_im().interpolated_rect(alpha);
+        }
+        if self.cam_returned {
+            cam.centre_on_pos(
+                self.player[&self.p].go.rect.centre(),
+                self.map.tiled_map.rect
+            );
+        }
+
+        self.map.draw(cam);
         if self.finished_count == 0 {
             for (a, p) in self.player.iter_mut() {
                 if *a == self.p {
@@ -164,7 +320,13 @@ impl Game {
             self.player[&self.p].draw(cam);
         }
         for o in self.objects.iter() {
-            cam.draw_rect(o.pr_im().get_pixel_correct_rect(), o.pr_im().colour);
+            let r = o.pr_im().interpolated_rect(alpha);
+            match o.shape() {
+                Some(shape) => for row in shape.rows(r) {
+                    cam.draw_rect(row, o.pr_im().colour);
+                },
+                None => cam.draw_rect(r, o.pr_im().colour),
+            }
         }
         for n in self.nested.iter() {
             cam.draw_rect(n.pr_im().rect, n.pr_im().colour);
@@ -179,58 +341,148 @@ impl Game {
             cam.draw_rect_static(
                 Rect::new(
                     0.0, 154.0,
-                    240.0 * (1.0 -(self.switch_time / SWITCH_TIME)),
+                    240.0 * (1.0 -(self.switch_time / self.levels[self.m].switch_time)),
                     10.0),
                 Colour::new(0, 0, 0, 190));
         }
     }
 
     fn next_lvl(&mut self) {
-        if self.m < self.maps.len() - 1 {
-            self.m += 1;
-            self.load_map();
+        if self.m < self.levels.len() - 1 {
+            self.pending_level = Some((self.m + 1, true));
         } else { self.level_complete = true; }
         self.map_loaded = false;
         self.cam_returned = true;
     }
 
+    /// swap in level `m`'s map, using the prefetched copy if there is one,
+    /// and let go of the old map's textures
+    fn switch_level<TL: TextureLoader>(&mut self, m: usize, snap_camera: bool, tm: &mut TL) -> Result<(), String> {
+        let map = match self.take_prefetched(m) {
+            Some(tiled_map) => Map::new_from_tiled(tiled_map, tm)?,
+            None => Map::new(&self.levels[m].map, tm)?,
+        };
+        std::mem::replace(&mut self.map, map).release(tm);
+        self.m = m;
+        self.load_map();
+        if snap_camera {
+            self.map_loaded = false;
+            self.cam_returned = true;
+        }
+        self.prefetch_level(m + 1);
+        Ok(())
+    }
+
+    fn prefetch_level(&mut self, m: usize) {
+        self.prefetched = None;
+        if !self.prefetch || m >= self.levels.len() { return; }
+        let path = self.levels[m].map.clone();
+        self.prefetched = Some((m, thread::spawn(move || tiled::Map::new(&path))));
+    }
+
+    /// a failed prefetch is dropped here, so the error is reported by loading the map again
+    fn take_prefetched(&mut self, m: usize) -> Option<tiled::Map> {
+        match self.prefetched.take() {
+            Some((i, handle)) if i == m => handle.join().ok().and_then(|r| r.ok()),
+            _ => None,
+        }
+    }
+
     fn load_map(&mut self) {
         self.map_loaded = true;
         self.cam_returned = false;
         self.p = Players::Heavy;
         self.switch_time = 0.0;
+        self.level_time = 0.0;
         self.finished_count = 0;
         self.nested.clear();
         self.objects.clear();
-        for ob_g in self.maps[self.m].tiled_map.obj_groups.iter() {
+        self.slopes.clear();
+        let tiles = self.map.tile_collision();
+        for r in tiles.rects {
+            self.objects.push(
+                Box::new(StaticObs::new(r))
+            );
+        }
+        for (r, shape) in tiles.shapes {
+            let mut obs = StaticObs::new(r);
+            obs.shape = Some(shape);
+            self.objects.push(Box::new(obs));
+        }
+        self.slopes.extend(tiles.slopes);
+        // objects go through the layer tree like tiles do, so their groups' offsets apply
+        // and hidden layers or ones with parallax don't add to the world
+        for d in self.map.tiled_map.draw_order() {
+            if d.kind != tiled::LayerKind::Objects || !d.info.visible { continue; }
+            if d.info.parallax.x != 1.0 || d.info.parallax.y != 1.0 { continue; }
+            let tiled_map = &self.map.tiled_map;
+            let ob_g = &tiled_map.obj_groups[d.index];
+            let offset = d.info.offset;
+            let place = |points: Vec<Vec2>| -> Vec<Vec2> {
+                points.into_iter().map(|p| tiled_map.object_to_world(p) + offset).collect()
+            };
+            // isometric maps draw rects as diamonds, so there they collide by their outline
+            let iso = matches!(tiled_map.orientation, tiled::Orientation::Isometric);
+            let bounds = |o: &tiled::Obj| -> (Rect, Option<Shape>) {
+                let points = place(corners(o.bounds()));
+                if iso {
+                    let (r, shape) = Shape::new(&points);
+                    (r, Some(shape))
+                } else {
+                    (Rect::new_from_vec2s(&points[0], &points[2]).floor(), None)
+                }
+            };
             for o in ob_g.objs.iter() {
+                let (r, shape) = bounds(o);
                 if check_bool("nested", &o.props) || check_bool("nested", &ob_g.props) {
                     self.nested.push(
-                        Nested::new(o.rect.floor())
+                        Nested::new(r)
                     );
                 }
                 if check_bool("static", &o.props) || check_bool("static", &ob_g.props) {
-                    self.objects.push(
-                        Box::new(StaticObs::new(o.rect.floor()))
-                    );
+                    let mut obs = StaticObs::new(r);
+                    obs.shape = shape.clone();
+                    self.objects.push(Box::new(obs));
                 }
                 if check_bool("fall", &o.props) || check_bool("fall", &ob_g.props) {
-                    self.objects.push(
-                        Box::new(DownObs::new(o.rect.floor()))
-                    );
+                    let mut obs = DownObs::new(r);
+                    obs.shape = shape.clone();
+                    self.objects.push(Box::new(obs));
                 }
                 if check_bool("push", &o.props) || check_bool("push", &ob_g.props) {
-                    self.objects.push(
-                        Box::new(GravObs::new(
-                            o.rect.floor(),
-                            get_int("weight", &o.props, 10) as f64 / 10.0
-                        ))
-                    );
+                    let mut obs = GravObs::new(r, get_int("weight", &o.props, 10) as f64 / 10.0);
+                    obs.shape = shape;
+                    self.objects.push(Box::new(obs));
+                }
+            }
+            // solid polylines are slopes the players walk along
+            for p in ob_g.polys.iter().filter(|p| !p.closed) {
+                if check_bool("static", &p.obj.props) || check_bool("static", &ob_g.props) {
+                    self.slopes.push(Slope::new(place(poly_points(p))));
+                }
+            }
+            // polygons and ellipses collide by their outline
+            for (o, points) in shape_outlines(ob_g) {
+                let (r, shape) = Shape::new(&place(points));
+                if check_bool("static", &o.props) || check_bool("static", &ob_g.props) {
+                    let mut obs = StaticObs::new(r);
+                    obs.shape = Some(shape.clone());
+                    self.objects.push(Box::new(obs));
+                }
+                if check_bool("fall", &o.props) || check_bool("fall", &ob_g.props) {
+                    let mut obs = DownObs::new(r);
+                    obs.shape = Some(shape.clone());
+                    self.objects.push(Box::new(obs));
+                }
+                if check_bool("push", &o.props) || check_bool("push", &ob_g.props) {
+                    let mut obs = GravObs::new(r, get_int("weight", &o.props, 10) as f64 / 10.0);
+                    obs.shape = Some(shape);
+                    self.objects.push(Box::new(obs));
                 }
             }
             for p in ob_g.points.iter() {
                 if check_bool("spawn", &p.props) {
-                    self.player_spawn = Vec2::new(p.rect.x, p.rect.y);
+                    self.player_spawn = tiled_map.object_to_world(p.rect.top_left()) + offset;
                 }
             }
         }
@@ -255,7 +507,7 @@ impl Game {
         let mut i: i32 = 0;
         while (i as usize) < self.objects.len() {
             if self.objects[i as usize].pr().rect.y >
-                self.maps[self.m].tiled_map.rect.h + self.objects[i as usize].pr().rect.h {
+                self.map.tiled_map.rect.h + self.objects[i as usize].pr().rect.h {
                 self.objects.remove(i as usize);
                 i-=1;
             }
@@ -263,11 +515,11 @@ impl Game {
         }
     
         if self.player[&self.p].pr_im().rect.y >
-            self.maps[self.m].tiled_map.rect.h + self.player[&self.p].pr_im().rect.h {
+            self.map.tiled_map.rect.h + self.player[&self.p].pr_im().rect.h {
             self.load_map();
         }
         if self.player[&self.p].pr_im().rect.x >
-            self.maps[self.m].tiled_map.rect.w + self.player[&self.p].pr_im().rect.w {
+            self.map.tiled_map.rect.w + self.player[&self.p].pr_im().rect.w {
                 self.swap_player();
                 self.finished_count += 1;
                 if self.finished_count == 2 {
@@ -281,13 +533,16 @@ fn phys_update(
     objs: &mut Vec<Box<dyn Phys>>, time: &f64,
     p: &mut Player,
     nested: &mut Vec<Nested>,
+    slopes: &[Slope],
 ) {
     for o in objs.iter_mut() {
+        o.pr().step_start = o.pr().s;
         o.pre_physics();
     }
     for n in nested.iter_mut() {
         n.pre_physics()
     }
+    p.pr().step_start = p.pr().s;
     p.pre_physics();
     
     for o in objs.iter_mut() {
@@ -306,9 +561,12 @@ fn phys_update(
     for n in nested.iter_mut() {
         n.phys_y(time);
     }
+    // phys_y forgets if the player was standing on something
+    let grounded = p.pr().y_collision;
     p.phys_y(time);
     
     coll(objs, p, nested);
+    land_on_slopes(p.pr(), slopes, grounded);
     
     for o in objs.iter_mut() {
         o.post_physics();
@@ -344,7 +602,7 @@ pub fn coll(
 }
 
 fn collision_checks(objs: &mut Vec<Box<dyn Phys>>) {
-    if objs.len() == 0 { return; }
+    if objs.is_empty() { return; }
     for i in 0..objs.len() - 1 {
         for j in i + 1..objs.len() {
             let a : &mut Box<dyn Phys>;
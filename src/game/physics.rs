@@ -15,6 +15,7 @@ pub struct PhysRect {
     pub a : Vec2,
     pub max_v : Vec2,
     pub prev_s: Vec2,
+    pub step_start: Vec2,
     pub last_update: LastUpdate,
     pub weight: f64,
     pub x_collision: bool,
@@ -38,6 +39,7 @@ impl PhysRect {
             a: Vec2::new(0.0, 0.0),
             max_v: Vec2::new(0.0, 0.0),
             prev_s: Vec2::new(rect.x, rect.y),
+            step_start: Vec2::new(rect.x, rect.y),
             last_update: LastUpdate::X,
             weight: 1.0,
             x_collision: false,
@@ -75,8 +77,17 @@ impl PhysRect {
         self.rect.round_pos()
     }
 
+    /// the rect between where it started the last step and where it is now, rounded for drawing
+    pub fn interpolated_rect(&self, alpha: f64) -> Rect {
+        let mut r = self.rect;
+        r.x = self.step_start.x + (self.s.x - self.step_start.x) * alpha;
+        r.y = self.step_start.y + (self.s.y - self.step_start.y) * alpha;
+        r.round_pos()
+    }
+
     pub fn set_pos(&mut self, pos: Vec2) {
         self.s = pos;
+        self.step_start = pos;
         self.rect.x = pos.x;
         self.rect.y = pos.y;
     }
@@ -98,19 +109,25 @@ pub trait Phys {
     fn phys_y(&mut self, time: &f64) {
         self.pr().update_y(time);
     }
-    fn collision(&mut self, other: &PhysRect) {
-        match self.pr().last_update {
+    /// the outline the body collides with, `None` to use its rect
+    fn shape(&self) -> Option<&Shape> { None }
+    fn collision(&mut self, other: &PhysRect, other_shape: Option<&Shape>) {
+        let pr = *self.pr_im();
+        let hit = |r: &Rect| overlapping(r, self.shape(), &other.rect, other_shape);
+        match pr.last_update {
             LastUpdate::X => {
+                let x = resolve_x(pr.prev_s.x, pr.rect, hit);
                 self.pr().x_collision = true;
-                self.pr().s.x = resolve_x(self.pr().prev_s.x, self.pr().rect, &other.rect);
+                self.pr().s.x = x;
                 self.pr().rect.x = self.pr().s.x;
                 self.pr().v.x = momentum(
                     self.pr().v.x, self.pr().weight, other.v.x, other.weight
                 )
             }
             LastUpdate::Y => {
+                let y = resolve_y(pr.prev_s.y, pr.rect, hit);
                 self.pr().y_collision = true;
-                self.pr().s.y = resolve_y(self.pr().prev_s.y, self.pr().rect, &other.rect);
+                self.pr().s.y = y;
                 self.pr().rect.y = self.pr().s.y;
                 self.pr().v.y = 0.0;/*momentum(
                     self.pr().v.y, self.pr().weight, other.v.y, other.weight
@@ -123,10 +140,10 @@ pub trait Phys {
 
 
 pub fn collision_update<A: Phys + ?Sized, B: Phys + ?Sized>(a: &mut A, b: &mut B) {
-    if a.pr().rect.colliding(&b.pr().rect) {
+    if overlapping(&a.pr_im().rect, a.shape(), &b.pr_im().rect, b.shape()) {
         let a_phys = *a.pr();
-        a.collision(b.pr());
-        b.collision(&a_phys);
+        a.collision(b.pr_im(), b.shape());
+        b.collision(&a_phys, a.shape());
     }
 }
 
@@ -137,25 +154,221 @@ fn momentum(u1: f64, m1: f64, u2: f64, m2: f64) -> f64 {
     ((m1 - m2)/tm)*u1 + ((m2*2.0)/tm)*u2
 }
 
-fn resolve_x(v: f64, a: Rect, b: &Rect) -> f64 {
+fn resolve_x(v: f64, a: Rect, hit: impl Fn(&Rect) -> bool) -> f64 {
     let mut a = a;
     let dir = (v - a.x).signum();
     while (v - a.x).signum() != dir {
         a.x += dir/2.0;
-        if !a.colliding(b) {
+        if !hit(&a) {
             return a.x;
         }
     }
     v
 }
-fn resolve_y(v: f64, a: Rect, b: &Rect) -> f64 {
+fn resolve_y(v: f64, a: Rect, hit: impl Fn(&Rect) -> bool) -> f64 {
     let mut a = a;
     let dir = (v - a.y).signum();
     while (v - a.y).signum() == dir {
         a.y += dir/5.0;
-        if !a.colliding(b) {
+        if !hit(&a) {
             return a.y;
         }
     }
     v
 }
+
+/// an outline for a body that isn't a rect, split into convex pieces so overlaps
+/// can be found with the separating axis test, points are relative to the body's rect
+#[derive(Clone)]
+pub struct Shape {
+    pieces: Vec<Vec<Vec2>>,
+}
+
+impl Shape {
+    /// the shape of a polygon given in world space, which may be concave, and the rect around it
+    pub fn new(points: &[Vec2]) -> (Rect, Shape) {
+        let (mut left, mut top, mut right, mut bottom) = (f64::MAX, f64::MAX, f64::MIN, f64::MIN);
+        for p in points {
+            left = left.min(p.x);
+            top = top.min(p.y);
+            right = right.max(p.x);
+            bottom = bottom.max(p.y);
+        }
+        let rect = Rect::new(left, top, right - left, bottom - top);
+        let local: Vec<Vec2> = points.iter().map(|p| Vec2::new(p.x - left, p.y - top)).collect();
+        (rect, Shape { pieces: convex_pieces(local) })
+    }
+
+    /// the pieces in world space for a body at `rect`
+    fn placed(&self, rect: &Rect) -> Vec<Vec<Vec2>> {
+        self.pieces.iter()
+            .map(|piece| piece.iter().map(|p| Vec2::new(p.x + rect.x, p.y + rect.y)).collect())
+            .collect()
+    }
+
+    /// a row of rects one pixel tall for each piece, to draw the shape of a body at `rect` with
+    pub fn rows(&self, rect: Rect) -> Vec<Rect> {
+        let mut rows = Vec::new();
+        for piece in self.placed(&rect) {
+            let mut y = rect.y;
+            while y < rect.y + rect.h {
+                // where the middle of the row crosses the piece's edges
+                let mid = y + 0.5;
+                let (mut left, mut right) = (f64::MAX, f64::MIN);
+                for i in 0..piece.len() {
+                    let (a, b) = (piece[i], piece[(i + 1) % piece.len()]);
+                    if (a.y <= mid) != (b.y <= mid) {
+                        let x = a.x + (mid - a.y) / (b.y - a.y) * (b.x - a.x);
+                        left = left.min(x);
+                        right = right.max(x);
+                    }
+                }
+                if left < right {
+                    rows.push(Rect::new(left, y, right - left, 1.0));
+                }
+                y += 1.0;
+            }
+        }
+        rows
+    }
+}
+
+/// points around an ellipse filling `rect`, clockwise from the right
+pub fn ellipse_points(rect: Rect) -> Vec<Vec2> {
+    const SIDES: usize = 24;
+    let centre = rect.centre();
+    (0..SIDES).map(|i| {
+        let (sin, cos) = (i as f64 / SIDES as f64 * std::f64::consts::TAU).sin_cos();
+        Vec2::new(centre.x + cos * rect.w / 2.0, centre.y + sin * rect.h / 2.0)
+    }).collect()
+}
+
+/// if two bodies overlap, by their shapes where they have them and their rects otherwise
+pub fn overlapping(a: &Rect, a_shape: Option<&Shape>, b: &Rect, b_shape: Option<&Shape>) -> bool {
+    if !a.colliding(b) { return false; }
+    if a_shape.is_none() && b_shape.is_none() { return true; }
+    let pieces = |r: &Rect, shape: Option<&Shape>| match shape {
+        Some(s) => s.placed(r),
+        None => vec![vec![
+            Vec2::new(r.x, r.y),
+            Vec2::new(r.x + r.w, r.y),
+            Vec2::new(r.x + r.w, r.y + r.h),
+            Vec2::new(r.x, r.y + r.h),
+        ]],
+    };
+    let b_pieces = pieces(b, b_shape);
+    pieces(a, a_shape).iter().any(|a| b_pieces.iter().any(|b| convex_overlap(a, b)))
+}
+
+/// the separating axis test, two convex polygons only miss each other
+/// if there is a gap between them along the normal of one of their edges
+fn convex_overlap(a: &[Vec2], b: &[Vec2]) -> bool {
+    for poly in [a, b] {
+        for i in 0..poly.len() {
+            let edge = poly[(i + 1) % poly.len()] - poly[i];
+            let axis = Vec2::new(-edge.y, edge.x);
+            let (a_min, a_max) = project(a, axis);
+            let (b_min, b_max) = project(b, axis);
+            if a_max <= b_min || b_max <= a_min {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+fn project(poly: &[Vec2], axis: Vec2) -> (f64, f64) {
+    poly.iter()
+        .map(|p| p.x * axis.x + p.y * axis.y)
+        .fold((f64::MAX, f64::MIN), |(min, max), d| (min.min(d), max.max(d)))
+}
+
+fn cross(a: Vec2, b: Vec2) -> f64 {
+    a.x * b.y - a.y * b.x
+}
+
+/// split a polygon into convex pieces, itself if it already is convex or else triangles by ear clipping
+fn convex_pieces(mut points: Vec<Vec2>) -> Vec<Vec<Vec2>> {
+    if points.len() < 3 { return Vec::new(); }
+    let n = points.len();
+    let turns: Vec<f64> = (0..n).map(|i| cross(
+        points[i] - points[(i + n - 1) % n],
+        points[(i + 1) % n] - points[i],
+    )).collect();
+    if turns.iter().all(|t| *t >= 0.0) || turns.iter().all(|t| *t <= 0.0) {
+        return vec![points];
+    }
+    // which way round the points go, so convex corners can be told from reflex ones
+    let area: f64 = (0..n).map(|i| cross(points[i], points[(i + 1) % n])).sum();
+    let mut pieces = Vec::new();
+    while points.len() > 3 {
+        let n = points.len();
+        let ear = (0..n).find(|&i| {
+            let (a, b, c) = (points[(i + n - 1) % n], points[i], points[(i + 1) % n]);
+            cross(b - a, c - b) * area > 0.0 && !(0..n)
+                .filter(|&j| j != i && j != (i + n - 1) % n && j != (i + 1) % n)
+                .any(|j| in_triangle(points[j], a, b, c))
+        });
+        match ear {
+            Some(i) => {
+                pieces.push(vec![points[(i + n - 1) % n], points[i], points[(i + 1) % n]]);
+                points.remove(i);
+            },
+            // only a polygon whose edges cross has no ears
+            None => break,
+        }
+    }
+    pieces.push(points);
+    pieces
+}
+
+fn in_triangle(p: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool {
+    let (d1, d2, d3) = (cross(b - a, p - a), cross(c - b, p - b), cross(a - c, p - c));
+    (d1 >= 0.0 && d2 >= 0.0 && d3 >= 0.0) || (d1 <= 0.0 && d2 <= 0.0 && d3 <= 0.0)
+}
+
+/// the steepest drop, in pixels down per pixel across, that a body standing on a slope follows it down
+const SLOPE_SNAP: f64 = 2.0;
+
+/// a walkable line, like a ramp, that bodies stand on wherever their feet are over it
+pub struct Slope {
+    points: Vec<Vec2>,
+}
+
+impl Slope {
+    pub fn new(points: Vec<Vec2>) -> Self {
+        Slope { points }
+    }
+}
+
+/// stand a body on the slope under the middle of its feet, if it came down onto it this step or is walking
+/// along it, `grounded` is if it stood on something last step, so it follows slopes down rather than running off
+pub fn land_on_slopes(pr: &mut PhysRect, slopes: &[Slope], grounded: bool) {
+    let foot = pr.rect.x + pr.rect.w / 2.0;
+    let prev_foot = pr.prev_s.x + pr.rect.w / 2.0;
+    let bottom = pr.rect.y + pr.rect.h;
+    let prev_bottom = pr.prev_s.y + pr.rect.h;
+    let snap = (foot - prev_foot).abs() * SLOPE_SNAP + 1.0;
+    let mut ground: Option<f64> = None;
+    for slope in slopes {
+        for line in slope.points.windows(2) {
+            let (a, b) = (line[0], line[1]);
+            let (left, right) = (a.x.min(b.x), a.x.max(b.x));
+            if left == right || foot < left || foot > right { continue; }
+            let height = |x: f64| a.y + (x.clamp(left, right) - a.x) / (b.x - a.x) * (b.y - a.y);
+            let surface = height(foot);
+            // bodies below a slope pass up through it
+            let was_above = prev_bottom <= height(prev_foot) + 0.5;
+            let lands = bottom >= surface || (grounded && pr.v.y >= 0.0 && surface - bottom <= snap);
+            if was_above && lands {
+                ground = Some(ground.map_or(surface, |g| g.min(surface)));
+            }
+        }
+    }
+    if let Some(y) = ground {
+        pr.s.y = y - pr.rect.h;
+        pr.rect.y = pr.s.y;
+        pr.v.y = 0.0;
+        pr.y_collision = true;
+    }
+}
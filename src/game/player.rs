@@ -1,8 +1,6 @@
 use super::helper::*;
 use super::physics::*;
 
-use sdl2::mixer::Music;
-
 //const ACC: Vec2 = Vec2 { x: 1000.0, y: 700.0 };
 //const MAX_V: Vec2 = Vec2 { x: 150.0, y: 600.0 };
 //const START_V : Vec2 = Vec2 { x: 0.0, y: -240.0  };
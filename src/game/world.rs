@@ -100,7 +100,7 @@ impl Phys for Nested {
     fn pr_im(&self) -> &PhysRect {
         &self.pr
     }
-    fn collision(&mut self, other: &PhysRect) {
+    fn collision(&mut self, other: &PhysRect, _: Option<&Shape>) {
         if self.pr().last_update == LastUpdate::Y && self.pr().s.y > other.s.y {
             self.col = Some(other.rect);
         }
@@ -110,6 +110,7 @@ impl Phys for Nested {
 
 pub struct StaticObs {
     pub pr: PhysRect,
+    pub shape: Option<Shape>,
 }
 
 impl StaticObs {
@@ -117,7 +118,8 @@ impl StaticObs {
         let mut pr = PhysRect::new_from_rect(r);
         pr.colour = Colour::new(30, 50, 30, 255);
         Self {
-            pr
+            pr,
+            shape: None,
         }
     }
 }
@@ -129,11 +131,15 @@ impl Phys for StaticObs {
     fn pr_im(&self) -> &PhysRect {
         &self.pr
     }
-    fn collision(&mut self, _: &PhysRect) {}
+    fn shape(&self) -> Option<&Shape> {
+        self.shape.as_ref()
+    }
+    fn collision(&mut self, _: &PhysRect, _: Option<&Shape>) {}
 }
 
 pub struct GravObs {
     pub pr: PhysRect,
+    pub shape: Option<Shape>,
 }
 
 impl GravObs {
@@ -144,7 +150,7 @@ impl GravObs {
         phys.weight = w;
         phys.friction = 0.95;
         phys.colour = Colour::new(50, 30, 30, 255);
-        GravObs { pr: phys }
+        GravObs { pr: phys, shape: None }
     }
 }
 
@@ -155,6 +161,9 @@ impl Phys for GravObs {
     fn pr_im(&self) -> &PhysRect {
         &self.pr
     }
+    fn shape(&self) -> Option<&Shape> {
+        self.shape.as_ref()
+    }
 }
 
 pub struct BrittleObs {
@@ -180,7 +189,7 @@ impl Phys for BrittleObs {
     fn pr_im(&self) -> &PhysRect {
         &self.pr
     }
-    fn collision(&mut self, other: &PhysRect) {
+    fn collision(&mut self, other: &PhysRect, _: Option<&Shape>) {
       //  if self.pr().last_update == LastUpdate::Y && self.pr().s.y > other.s.y {
       //      self.pr.a.y = 50.0;
       //  }
@@ -189,6 +198,7 @@ impl Phys for BrittleObs {
 
 pub struct DownObs {
     pub pr: PhysRect,
+    pub shape: Option<Shape>,
 }
 
 impl DownObs {
@@ -197,7 +207,7 @@ impl DownObs {
         //phys.a.y = 100.0;
         phys.weight = 0.05;
         phys.friction = 0.95;
-        Self { pr: phys }
+        Self { pr: phys, shape: None }
     }
 }
 
@@ -208,6 +218,9 @@ impl Phys for DownObs {
     fn pr_im(&self) -> &PhysRect {
         &self.pr
     }
+    fn shape(&self) -> Option<&Shape> {
+        self.shape.as_ref()
+    }
     fn pre_physics(&mut self) {
         self.pr().v.y = if self.pr.y_collision {
             160.0
@@ -215,7 +228,7 @@ impl Phys for DownObs {
             0.0
         };
     } 
-    fn collision(&mut self, other: &PhysRect) {
+    fn collision(&mut self, other: &PhysRect, _: Option<&Shape>) {
         if self.pr().last_update == LastUpdate::Y {
             self.pr().y_collision = true;
         }
@@ -0,0 +1,152 @@
+//! run the game without a window, so levels can be loaded and stepped in tests and tools
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+
+use geometry::{Rect, Vec2};
+use crate::{resource, TextureLoader, camera::Camera, input::Input, game::Game};
+use crate::timestep::{FixedStep, STEP};
+use crate::replay::Replay;
+
+const VIEW: Rect = Rect { x: 0.0, y: 0.0, w: 240.0, h: 160.0 };
+const WINDOW: Vec2 = Vec2 { x: 720.0, y: 480.0 };
+
+/// hands out `resource::Texture`s with the real image size but never decodes the pixels
+pub struct HeadlessTextures {
+    loaded_texture_paths: HashMap<String, usize>,
+    sizes: Vec<(u32, u32)>,
+    ref_counts: Vec<u32>,
+    font_families: HashMap<String, usize>,
+}
+
+impl HeadlessTextures {
+    pub fn new() -> Self {
+        HeadlessTextures {
+            loaded_texture_paths: HashMap::new(),
+            sizes: Vec::new(),
+            ref_counts: Vec::new(),
+            font_families: HashMap::new(),
+        }
+    }
+
+    pub fn is_loaded(&self, path: &str) -> bool {
+        self.loaded_texture_paths.contains_key(path)
+    }
+}
+
+impl Default for HeadlessTextures {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// read the width and height out of a png's IHDR chunk
+fn png_size(path: &str) -> Result<(u32, u32), String> {
+    let mut header = [0u8; 24];
+    File::open(path)
+        .and_then(|mut f| f.read_exact(&mut header))
+        .map_err(|e| format!("failed to read {}: {}", path, e))?;
+    if header[..8] != [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A] || &header[12..16] != b"IHDR" {
+        return Err(format!("{} is not a png file", path));
+    }
+    Ok((
+        u32::from_be_bytes([header[16], header[17], header[18], header[19]]),
+        u32::from_be_bytes([header[20], header[21], header[22], header[23]]),
+    ))
+}
+
+impl TextureLoader for HeadlessTextures {
+    fn load(&mut self, path: &str) -> Result<resource::Texture, String> {
+        let id = match self.loaded_texture_paths.get(path) {
+            Some(id) => *id,
+            None => {
+                self.sizes.push(png_size(path)?);
+                self.ref_counts.push(0);
+                self.loaded_texture_paths.insert(path.to_string(), self.sizes.len() - 1);
+                self.sizes.len() - 1
+            }
+        };
+        self.ref_counts[id] += 1;
+        Ok(resource::Texture {
+            id,
+            width: self.sizes[id].0,
+            height: self.sizes[id].1,
+        })
+    }
+
+    fn unload(&mut self, path: &str) {
+        if let Some(id) = self.loaded_texture_paths.get(path) {
+            self.ref_counts[*id] -= 1;
+            if self.ref_counts[*id] == 0 {
+                self.loaded_texture_paths.remove(path);
+            }
+        }
+    }
+
+    fn reload(&mut self, path: &str) -> Result<(), String> {
+        if let Some(id) = self.loaded_texture_paths.get(path) {
+            self.sizes[*id] = png_size(path)?;
+        }
+        Ok(())
+    }
+
+    fn load_font(&mut self, family: &str) -> Result<resource::Font, String> {
+        let next = self.font_families.len();
+        let id = *self.font_families.entry(family.to_string()).or_insert(next);
+        Ok(resource::Font { id })
+    }
+}
+
+/// a frontend that drives `Game` the same way `main` does, but throws the draws away
+pub struct Headless {
+    pub game: Game,
+    pub cam: Camera,
+    pub timestep: FixedStep,
+    pub textures: HeadlessTextures,
+}
+
+impl Headless {
+    pub fn new() -> Result<Self, String> {
+        let mut textures = HeadlessTextures::new();
+        Ok(Headless {
+            game: Game::new(&mut textures)?,
+            cam: Camera::new(VIEW, WINDOW),
+            timestep: FixedStep::new(STEP),
+            textures,
+        })
+    }
+
+    /// run a single physics step
+    pub fn step(&mut self, input: &Input) -> Result<(), String> {
+        if self.game.game_complete() {
+            return Ok(());
+        }
+        self.game.stream_levels(&mut self.textures)?;
+        self.game.update_camera(&mut self.cam);
+        self.game.update(&STEP, input);
+        self.cam.update(&STEP);
+        Ok(())
+    }
+
+    /// run however many steps fit in `frame` seconds, then draw like a real frame would
+    pub fn frame(&mut self, frame: f64, input: &Input) -> Result<(), String> {
+        self.timestep.add_frame(frame);
+        while self.timestep.step() {
+            self.step(input)?;
+        }
+        if !self.game.game_complete() {
+            self.game.draw(&mut self.cam, self.timestep.alpha());
+            self.cam.drain_draws();
+        }
+        Ok(())
+    }
+    /// restart at the replay's level and run every step it recorded
+    pub fn play(&mut self, replay: &Replay) -> Result<(), String> {
+        self.game.goto_level(replay.level, &mut self.textures)?;
+        for input in replay.inputs() {
+            self.step(&input)?;
+        }
+        Ok(())
+    }
+}
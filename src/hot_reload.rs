@@ -0,0 +1,97 @@
+//! notice files changing on disk by polling their modification times, so levels can be edited while the game runs
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+pub struct Watcher {
+    dirs: Vec<PathBuf>,
+    /// files outside of `dirs` that are also watched
+    files: Vec<PathBuf>,
+    modified: HashMap<PathBuf, SystemTime>,
+    interval: Duration,
+    last_poll: Instant,
+}
+
+impl Watcher {
+    /// watch every file under `dirs`, the files already there aren't reported until they change
+    pub fn new(dirs: &[&str], interval: Duration) -> Self {
+        let mut w = Watcher {
+            dirs: dirs.iter().map(PathBuf::from).collect(),
+            files: Vec::new(),
+            modified: HashMap::new(),
+            interval,
+            last_poll: Instant::now(),
+        };
+        w.changed();
+        w
+    }
+
+    /// also watch a file that may be outside of the watched directories
+    pub fn watch(&mut self, path: &str) {
+        let path = PathBuf::from(path);
+        if self.files.contains(&path) { return; }
+        if let Some(time) = modified_time(&path) {
+            self.modified.entry(path.clone()).or_insert(time);
+        }
+        self.files.push(path);
+    }
+
+    /// the files added or changed since the last check, checks at most once per interval
+    pub fn poll(&mut self) -> Vec<String> {
+        if self.last_poll.elapsed() < self.interval {
+            return Vec::new();
+        }
+        self.last_poll = Instant::now();
+        self.changed()
+    }
+
+    /// check every watched file now
+    pub fn changed(&mut self) -> Vec<String> {
+        let mut files = self.files.clone();
+        for dir in self.dirs.iter() {
+            list_files(dir, &mut files);
+        }
+        files.sort();
+        files.dedup();
+        let mut changed = Vec::new();
+        for file in files {
+            match modified_time(&file) {
+                Some(time) => if self.modified.insert(file.clone(), time) != Some(time) {
+                    changed.push(file.to_string_lossy().to_string());
+                },
+                // deleted files are reported again once they are back
+                None => { self.modified.remove(&file); },
+            }
+        }
+        changed
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn list_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(e) => e,
+        Err(_) => { return; },
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            list_files(&path, files);
+        } else {
+            files.push(path);
+        }
+    }
+}
+
+/// if two paths name the same file, even when written differently like `maps/../textures/a.png` and `textures/a.png`
+pub fn same_file(a: &str, b: &str) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
@@ -1,7 +1,10 @@
 //! take sdl2 events and update a struct of bools for required controls
 
+#[cfg(feature = "sdl")]
 use sdl2::event::Event;
+#[cfg(feature = "sdl")]
 use sdl2::keyboard::Scancode;
+#[cfg(feature = "sdl")]
 use sdl2::mouse::MouseButton;
 
 /// Holds mouse input info
@@ -59,6 +62,37 @@ impl Input {
         }
     }
 
+    /// pack the buttons into bits, the mouse is left out
+    pub fn to_bits(&self) -> u16 {
+        [
+            self.up, self.down, self.left, self.right,
+            self.a, self.b, self.restart,
+            self.debug_1, self.debug_2, self.debug_3,
+        ].iter()
+         .enumerate()
+         .fold(0, |bits, (i, b)| if *b { bits | (1 << i) } else { bits })
+    }
+
+    pub fn from_bits(bits: u16) -> Self {
+        let b = |i: u16| bits & (1 << i) != 0;
+        Input {
+            up      : b(0),
+            down    : b(1),
+            left    : b(2),
+            right   : b(3),
+            a       : b(4),
+            b       : b(5),
+            restart : b(6),
+            debug_1 : b(7),
+            debug_2 : b(8),
+            debug_3 : b(9),
+            mouse   : Mouse::new(),
+        }
+    }
+}
+
+#[cfg(feature = "sdl")]
+impl Input {
     pub fn handle_event(&mut self, event: &Event) {
         if event.is_keyboard() {
             self.handle_keyboard(event);
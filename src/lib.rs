@@ -1,40 +1,17 @@
-use sdl2::controller::GameController;
-use sdl2::render::{TextureCreator, Texture, Canvas};
-use sdl2::video::Window;
-use sdl2::image::LoadTexture;
-use sdl2::pixels::Color;
-use sdl2::ttf;
-
-use std::collections::HashMap;
-use std::path::Path;
-use std::clone::Clone;
-
 pub mod input;
 use geometry::*;
 pub mod camera;
 pub mod game;
+pub mod headless;
+pub mod hot_reload;
+pub mod replay;
+pub mod text;
+pub mod timestep;
 
-trait RectConversion {
-    fn new_from_sdl_rect(sdl_rect : &sdl2::rect::Rect) -> Self;
-    fn to_sdl_rect(&self) -> sdl2::rect::Rect;
-}
-
-impl RectConversion for Rect{
-    /// Use an `sdl2::rect::Rect` to construct a `Rect`
-    fn new_from_sdl_rect(sdl_rect : &sdl2::rect::Rect) -> Self {
-        Rect {
-            x: sdl_rect.x as f64,
-            y: sdl_rect.y as f64,
-            w: sdl_rect.w as f64,
-            h: sdl_rect.h as f64
-        }
-    }
-    
-    /// construct an `sdl2::rect::Rect` using this `Rect`
-    fn to_sdl_rect(&self) -> sdl2::rect::Rect {
-        sdl2::rect::Rect::new(self.x as i32, self.y as i32, self.w as u32, self.h as u32)
-    }
-}
+#[cfg(feature = "sdl")]
+mod sdl;
+#[cfg(feature = "sdl")]
+pub use sdl::*;
 
 pub mod resource {
 //! represent sdl2 textures and fonts as cheap structs that hold indexes for resource managers
@@ -53,10 +30,10 @@ pub mod resource {
 
 #[derive(Clone, Copy)]
 pub struct Colour {
-    r: u8,
-    g: u8,
-    b: u8,
-    a: u8
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8
 }
 
 impl Colour {
@@ -77,14 +54,6 @@ impl Colour {
     pub fn black() -> Colour {
         Self::new(0, 0, 0, 255)
     }
-    pub fn to_sdl2_colour(&self) -> Color {
-        Color {
-            r: self.r,
-            g: self.g,
-            b: self.b,
-            a: self.a,
-        }
-    }
 }
 
 #[derive(Clone, Copy)]
@@ -93,7 +62,9 @@ pub struct GameObject {
     pub rect: Rect,
     pub tex_rect: Rect,
     pub parallax: Vec2,
-    pub colour: Colour
+    pub colour: Colour,
+    /// clockwise degrees around the centre of `rect`, applied after flips from a negative `tex_rect` size
+    pub rotation: f64,
 }
 
 impl GameObject {
@@ -105,6 +76,7 @@ impl GameObject {
             tex_rect : r,
             parallax: Vec2::new(1.0, 1.0),
             colour: Colour::white(),
+            rotation: 0.0,
         }
     }
     pub fn new(texture : resource::Texture, rect : Rect, tex_rect: Rect, parallax : Vec2, colour: Colour) -> Self {
@@ -114,6 +86,7 @@ impl GameObject {
             tex_rect,
             parallax,
             colour,
+            rotation: 0.0,
         }
     }
 }
@@ -125,6 +98,7 @@ pub struct TextureDraw {
     pub tex_rect : Rect,
     pub colour : Colour,
     pub tex  : resource::Texture,
+    pub rotation : f64,
 }
 
 impl TextureDraw {
@@ -133,169 +107,22 @@ impl TextureDraw {
             draw_rect,
             tex_rect,
             colour,
-            tex
-        }
-    }
-}
-
-/// stores textures that are referenced by a `resource::Texture` object
-pub struct TextureManager<'a, T> {
-    texture_creator : &'a TextureCreator<T>,
-    loaded_texture_paths : HashMap<String,  usize>,
-    textures     : Vec<Texture<'a>>,
-}
-
-impl<'a, T> TextureManager<'a, T> {
-    pub fn new(tex_creator: &'a TextureCreator<T>) -> Self {
-
-        TextureManager {
-            texture_creator : tex_creator,
-            loaded_texture_paths: HashMap::new(),
-            textures : Vec::new(),
-        }
-    }
-/// load a texture to memory and get a `resource::Texture` object that references it
-    pub fn load(&mut self, path : &str) -> Result<resource::Texture, String> {
-        let tex_index = match self.loaded_texture_paths.contains_key(path) {
-            true => self.loaded_texture_paths[path],
-            false => {
-                self.textures.push(self.texture_creator.load_texture(path)?);
-                self.loaded_texture_paths.insert(path.to_string(), self.textures.len() - 1);
-
-                println!("loaded: {}", path);
-
-                self.textures.len() - 1
-            },
-        };
-        let last_tex = &self.textures[tex_index];
-        Ok(
-        resource::Texture {
-            id: tex_index,
-            width: last_tex.query().width,
-            height: last_tex.query().height,
-        })
-
-    }
-/// draw a `GameObject` to the canvas
-    pub fn draw(&mut self, canvas : &mut Canvas<Window>, tex_draw: TextureDraw) -> Result<(), String> {
-        self.textures[tex_draw.tex.id].set_color_mod(
-            tex_draw.colour.r,
-            tex_draw.colour.g,
-            tex_draw.colour.b
-        );
-        self.textures[tex_draw.tex.id].set_alpha_mod(tex_draw.colour.a);
-        let mut h_f = false;
-        let mut v_f = false;
-        let mut t_r = tex_draw.tex_rect;
-        if tex_draw.tex_rect.w < 0.0 {
-            h_f = true;
-            t_r.w *= -1.0;
-        }
-        if tex_draw.tex_rect.h < 0.0 {
-            v_f = true;
-            t_r.h *= -1.0;
+            tex,
+            rotation: 0.0,
         }
-        canvas.copy_ex(
-            &self.textures[tex_draw.tex.id],
-            t_r.to_sdl_rect(),
-            tex_draw.draw_rect.to_sdl_rect(),
-            0.0,
-            None,
-            h_f,
-            v_f,
-        )
     }
-
-    pub fn draw_rect(&self, canvas : &mut Canvas<Window>, rect : geometry::Rect, colour :  Colour) -> Result<(), String> {
-        canvas.set_draw_color(Color::RGBA(colour.r, colour.g, colour.b, colour.a));
-        canvas.fill_rect(rect.to_sdl_rect())?;
-        Ok(())
-    }
-}
-
-/// can be returned by `FontManager`, stores an sdl2 texture and a rect for drawing to a canvas
-pub struct TextDraw<'a> {
-    pub tex  : sdl2::render::Texture<'a>,
-    pub rect : sdl2::rect::Rect,
-}
-
-const FONT_LOAD_SIZE : u16 = 128;
-
-/// Stores 'sdl2::ttf::Font' and returns textures or draws them
-pub struct FontManager<'a, T> {
-    texture_creator : &'a TextureCreator<T>,
-    ttf_context: &'a ttf::Sdl2TtfContext,
-    loaded_font_paths : HashMap<String, usize>,
-    pub fonts : Vec<ttf::Font<'a, 'static>>,
 }
 
-impl<'a, T> FontManager<'a, T> {
-    pub fn new(ttf_context : &'a ttf::Sdl2TtfContext, texture_creator : &'a TextureCreator<T>) -> Result<Self, String> {
-        Ok(FontManager {
-            texture_creator,
-            ttf_context,
-            loaded_font_paths: HashMap::new(),
-            fonts : Vec::new(),
-        })
-    }
-
-    pub fn load_font(&mut self, path : &Path) -> Result<resource::Font, String>{
-        let path_string = path.to_string_lossy().to_string();
-        let font_index = match self.loaded_font_paths.contains_key(&path_string) {
-            true => self.loaded_font_paths[&path_string],
-            false => {
-                self.fonts.push(
-                    match self.ttf_context.load_font(path, FONT_LOAD_SIZE) {
-                        Ok(s) => s,
-                        Err(e) => { return Err(e.to_string()); }
-                    }
-                );
-                self.loaded_font_paths.insert(path_string, self.fonts.len() - 1);
-                self.fonts.len() - 1
-            }
-        };
-        Ok(
-            resource::Font {
-            id: font_index,
-        })
-    }
-    /// return a `TextDraw` that has a corrected `rect.width` based on the supplied height and the rendered font
-    pub fn get_draw(&self, font: &resource::Font, text: &str, height : u32, colour : Color) -> Result<TextDraw, String> {
-        self.get_draw_at_vec2(font, text, height, Vec2::new(0.0, 0.0), colour)
-    }
-
-    pub fn get_draw_at_vec2(&self, font: &resource::Font, text: &str, height : u32, pos: Vec2, colour: Color) -> Result<TextDraw, String> {
-        if text.len() == 0 { Err("text length should be greater than 0")?; }
-        let surface = match self.fonts[font.id]
-            .render(text)
-            .blended(colour) {
-                Ok(s) => s,
-                Err(e) => return Err(e.to_string()),
-        };
-        let tex = match self.texture_creator.create_texture_from_surface(&surface) {
-            Ok(t) => t,
-            Err(e) => { return Err(e.to_string()); },
-        };
-        let ratio = tex.query().height as f64 / tex.query().width as f64;
-        Ok(
-        TextDraw {
-            tex,
-            rect:
-             sdl2::rect::Rect::new(
-                pos.x as i32,
-                pos.y as i32,
-                (height as f64 / ratio) as u32,
-                height
-             ),
-        })
-    }
 
-    /// draws the supplied text to the canvas in the supplied font at the given height and position
-    pub fn draw(&self, canvas : &mut Canvas<Window>, font : &resource::Font, text: &str, height : u32, pos : Vec2, colour : Color) -> Result<(), String> {
-        if text.len() == 0 { return Ok(()); }
-        let mut tex_draw = self.get_draw(font, text, height, colour)?;
-        tex_draw.rect.x = pos.x as i32;
-        tex_draw.rect.y = pos.y as i32;
-        canvas.copy(&tex_draw.tex, None, tex_draw.rect)
-    }
+/// anything that can turn an image file into a `resource::Texture` and a font family into a `resource::Font`,
+/// lets the game load its assets without knowing about sdl2
+pub trait TextureLoader {
+    /// every `load` of a path should be matched by an `unload` once the texture isn't needed
+    fn load(&mut self, path: &str) -> Result<resource::Texture, String>;
+    /// the texture is freed once every `load` of the path has been unloaded
+    fn unload(&mut self, path: &str);
+    /// read a loaded texture's file again, keeping its `resource::Texture` id, paths that aren't loaded are ignored
+    fn reload(&mut self, path: &str) -> Result<(), String>;
+    /// find the font for a family name, loading it the first time the family is asked for
+    fn load_font(&mut self, family: &str) -> Result<resource::Font, String>;
 }
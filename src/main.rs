@@ -1,4 +1,4 @@
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use sdl2::{
     event::Event,
@@ -11,9 +11,25 @@ use sdl2::{
 };
 
 use geometry::Vec2;
-use coupled_explorers::{TextureManager, camera::*, input::Input, game::Game, game::Players, GameObject};
+use coupled_explorers::{TextureManager, FontManager, Resources, camera::*, input::Input, game::Game, game::Players, GameObject};
+use coupled_explorers::timestep::{FixedStep, STEP};
+use coupled_explorers::replay::Replay;
+use coupled_explorers::hot_reload::Watcher;
 
 pub fn main() -> Result<(), String> {
+    let args: Vec<String> = std::env::args().collect();
+    let record_path = arg_value(&args, "--record");
+    let playback = match arg_value(&args, "--replay") {
+        Some(path) => Some(Replay::load(&path)?),
+        None => None,
+    };
+    let dev = args.iter().any(|a| a == "--dev");
+    // reload maps and textures as they are edited
+    let mut watcher = match dev {
+        true => Some(Watcher::new(&["maps", "textures"], Duration::from_millis(500))),
+        false => None,
+    };
+
     let sdl_context = sdl2::init()?;
     let video_subsystem = sdl_context.video()?;
     let _image_context = image::init(image::InitFlag::PNG);
@@ -59,44 +75,69 @@ pub fn main() -> Result<(), String> {
 
     let texture_creator = canvas.texture_creator();
     let mut texture_manager = TextureManager::new(&texture_creator);
-    let end_screen = GameObject::new_from_tex(texture_manager.load("textures/end.png")?);
-    let mut game = Game::new(&mut texture_manager)?;
+    let ttf_context = sdl2::ttf::init().map_err(|e| e.to_string())?;
+    let mut font_manager = FontManager::new(&ttf_context, &texture_creator)?;
+    let mut res = Resources { textures: &mut texture_manager, fonts: &mut font_manager };
+    let end_screen = GameObject::new_from_tex(res.textures.load("textures/end.png")?);
+    let mut game = Game::new(&mut res)?;
+    if let Some(replay) = &playback {
+        game.goto_level(replay.level, &mut res)?;
+    }
+    let mut replay_inputs = playback.as_ref().map(|r| r.inputs());
+    let mut recording = Replay::new(game.level(), 0);
     
     canvas.set_blend_mode(sdl2::render::BlendMode::Blend);
     
     let mut event_pump = sdl_context.event_pump()?;
     let mut input = Input::new();
+    let mut timestep = FixedStep::new(STEP);
     let mut prev_frame : f64 = 0.0;
+    let mut paused = false;
+    let mut single_step = false;
     'running: loop {
         let start_time = Instant::now();
         for event in event_pump.poll_iter() {
             match event {
                 Event::Quit { .. } | Event::KeyDown {  keycode: Some(Keycode::Escape), ..} => break 'running,
+                Event::KeyDown { keycode: Some(Keycode::F5), repeat: false, .. } => paused = !paused,
+                Event::KeyDown { keycode: Some(Keycode::F6), .. } => single_step = true,
                 _ => { }
             }
             input.handle_event(&event);
             handle_event(&event, &mut canvas, &mut cam)?;
         }
-        
-        canvas.set_draw_color(Color::RGB(255, 255, 255));
-        canvas.clear();
 
-        if !game.game_complete() {
-            game.draw(&mut cam);
-        } else {
-            cam.draw(&end_screen);
+        if let Some(w) = watcher.as_mut() {
+            let changed = w.poll();
+            if !changed.is_empty() {
+                if let Err(e) = game.hot_reload(&changed, &mut res) {
+                    eprintln!("{}", e);
+                }
+            }
+            for f in game.watched_files() {
+                w.watch(&f);
+            }
         }
-        for d in cam.drain_draws() {
-            match d {
-                CamDraw::Tex(t)  => texture_manager.draw(&mut canvas, t)?,
-                CamDraw::Rect(r) => texture_manager.draw_rect(&mut canvas, r.rect, r.colour)?,
+
+        // map warnings are only worth reading while editing the maps
+        if dev {
+            for w in game.take_warnings() {
+                eprintln!("warning: {}", w);
             }
         }
-      
-        canvas.present();
 
-        if !game.game_complete() {
-            game.update(&prev_frame, &input);
+        timestep.add_frame(prev_frame);
+        while timestep.step() {
+            if game.game_complete() || (paused && !single_step) { continue; }
+            single_step = false;
+            let step_input = match replay_inputs.as_mut().and_then(|i| i.next()) {
+                Some(i) => i,
+                None => input,
+            };
+            recording.record(&step_input);
+            game.stream_levels(&mut res)?;
+            game.update_camera(&mut cam);
+            game.update(&STEP, &step_input);
             match game.jumped() {
                 Some(Players::Quick) => {
                     sdl2::mixer::Channel::all().play(&light_jmp, 0);
@@ -106,22 +147,51 @@ pub fn main() -> Result<(), String> {
                 }
                 _ => (),
             }
-            cam.update(&prev_frame);
-        } else {
+            cam.update(&STEP);
+        }
+        if game.game_complete() {
             cam.set_offset(Vec2::new(0.0, 0.0));
         }
+        
+        canvas.set_draw_color(Color::RGB(255, 255, 255));
+        canvas.clear();
+
+        if !game.game_complete() {
+            game.draw(&mut cam, timestep.alpha());
+        } else {
+            cam.draw(&end_screen);
+        }
+        for d in cam.drain_draws() {
+            match d {
+                CamDraw::Tex(t)  => res.textures.draw(&mut canvas, t)?,
+                CamDraw::Rect(r) => res.textures.draw_rect(&mut canvas, r.rect, r.colour)?,
+                CamDraw::Text(t) => res.fonts.draw_text_box(&mut canvas, &t)?,
+            }
+        }
+      
+        canvas.present();
  
         prev_frame = start_time.elapsed().as_secs_f64();
-        if prev_frame > 0.1 {
-            prev_frame = 0.0;
-        }
 
         //println!("prev frame: {} fps", 1.0/prev_frame);
     }
 
+    if let Some(path) = record_path {
+        recording.save(&path)?;
+        println!("saved replay: {}", path);
+    }
+
     Ok(())
 }
 
+/// get the argument following `flag`, ie `--record run.replay`
+fn arg_value(args: &[String], flag: &str) -> Option<String> {
+    args.iter()
+        .position(|a| a == flag)
+        .and_then(|i| args.get(i + 1))
+        .cloned()
+}
+
 
 fn handle_event(event: &Event, canvas: &mut Canvas<Window>, cam: &mut Camera) -> Result<(), String> {
     match event {
@@ -0,0 +1,117 @@
+//! record the input for every physics step so a run can be played back exactly
+//!
+//! replays are plain text:
+//! ```text
+//! coupled explorers replay 1
+//! level 0
+//! seed 0
+//! step 0.008333333333333333
+//! 0 120
+//! 8 45
+//! ```
+//! after the header each line is an `Input` packed with `Input::to_bits`,
+//! followed by how many steps in a row it was held for
+
+use std::fs;
+
+use crate::input::Input;
+use crate::timestep::STEP;
+
+const HEADER: &str = "coupled explorers replay 1";
+
+pub struct Replay {
+    /// the level the recording started on
+    pub level: usize,
+    /// kept with the run for anything random in the simulation, the game doesn't use one yet
+    pub seed: u64,
+    /// the physics step the run was recorded with
+    pub step: f64,
+    runs: Vec<(u16, u32)>,
+}
+
+impl Replay {
+    pub fn new(level: usize, seed: u64) -> Replay {
+        Replay {
+            level,
+            seed,
+            step: STEP,
+            runs: Vec::new(),
+        }
+    }
+
+    /// add the input used for the next physics step
+    pub fn record(&mut self, input: &Input) {
+        let bits = input.to_bits();
+        match self.runs.last_mut() {
+            Some((b, count)) if *b == bits => *count += 1,
+            _ => self.runs.push((bits, 1)),
+        }
+    }
+
+    /// number of steps recorded
+    pub fn len(&self) -> usize {
+        self.runs.iter().map(|(_, c)| *c as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.runs.is_empty()
+    }
+
+    /// every recorded input in step order
+    pub fn inputs(&self) -> impl Iterator<Item = Input> + '_ {
+        self.runs
+            .iter()
+            .flat_map(|(bits, count)| std::iter::repeat_n(Input::from_bits(*bits), *count as usize))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_text())
+            .map_err(|e| format!("failed to write replay {}: {}", path, e))
+    }
+
+    pub fn load(path: &str) -> Result<Replay, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read replay {}: {}", path, e))?;
+        Self::from_text(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "{}\nlevel {}\nseed {}\nstep {}\n",
+            HEADER, self.level, self.seed, self.step
+        );
+        for (bits, count) in self.runs.iter() {
+            text.push_str(&format!("{} {}\n", bits, count));
+        }
+        text
+    }
+
+    pub fn from_text(text: &str) -> Result<Replay, String> {
+        let mut lines = text.lines().enumerate();
+        match lines.next() {
+            Some((_, HEADER)) => (),
+            _ => { return Err(String::from("not a replay file, or made by a different version")); }
+        }
+        let mut replay = Replay::new(0, 0);
+        for (n, line) in lines {
+            let bad_line = || format!("line {}: could not parse \"{}\"", n + 1, line);
+            let (a, b) = line.trim().split_once(' ').ok_or_else(bad_line)?;
+            match a {
+                "level" => replay.level = b.parse().map_err(|_| bad_line())?,
+                "seed" => replay.seed = b.parse().map_err(|_| bad_line())?,
+                "step" => replay.step = b.parse().map_err(|_| bad_line())?,
+                _ => replay.runs.push((
+                    a.parse().map_err(|_| bad_line())?,
+                    b.parse().map_err(|_| bad_line())?,
+                )),
+            }
+        }
+        if replay.step != STEP {
+            return Err(format!(
+                "recorded with a physics step of {}s, but the game steps at {}s",
+                replay.step, STEP
+            ));
+        }
+        Ok(replay)
+    }
+}
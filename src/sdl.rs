@@ -0,0 +1,320 @@
+use sdl2::render::{TextureCreator, Texture, Canvas};
+use sdl2::video::Window;
+use sdl2::image::LoadTexture;
+use sdl2::pixels::Color;
+use sdl2::ttf;
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use geometry::*;
+use crate::{resource, Colour, TextureDraw, TextureLoader};
+use crate::text::{self, TextBox};
+
+trait RectConversion {
+    fn new_from_sdl_rect(sdl_rect : &sdl2::rect::Rect) -> Self;
+    fn to_sdl_rect(&self) -> sdl2::rect::Rect;
+}
+
+impl RectConversion for Rect{
+    /// Use an `sdl2::rect::Rect` to construct a `Rect`
+    fn new_from_sdl_rect(sdl_rect : &sdl2::rect::Rect) -> Self {
+        Rect {
+            x: sdl_rect.x as f64,
+            y: sdl_rect.y as f64,
+            w: sdl_rect.w as f64,
+            h: sdl_rect.h as f64
+        }
+    }
+    
+    /// construct an `sdl2::rect::Rect` using this `Rect`
+    fn to_sdl_rect(&self) -> sdl2::rect::Rect {
+        sdl2::rect::Rect::new(self.x as i32, self.y as i32, self.w as u32, self.h as u32)
+    }
+}
+
+impl Colour {
+    pub fn to_sdl2_colour(&self) -> Color {
+        Color {
+            r: self.r,
+            g: self.g,
+            b: self.b,
+            a: self.a,
+        }
+    }
+}
+
+/// stores textures that are referenced by a `resource::Texture` object
+pub struct TextureManager<'a, T> {
+    texture_creator : &'a TextureCreator<T>,
+    loaded_texture_paths : HashMap<String,  usize>,
+    textures     : Vec<Option<Texture<'a>>>,
+    ref_counts   : Vec<u32>,
+}
+
+impl<'a, T> TextureManager<'a, T> {
+    pub fn new(tex_creator: &'a TextureCreator<T>) -> Self {
+
+        TextureManager {
+            texture_creator : tex_creator,
+            loaded_texture_paths: HashMap::new(),
+            textures : Vec::new(),
+            ref_counts : Vec::new(),
+        }
+    }
+/// load a texture to memory and get a `resource::Texture` object that references it
+    pub fn load(&mut self, path : &str) -> Result<resource::Texture, String> {
+        let tex_index = match self.loaded_texture_paths.contains_key(path) {
+            true => self.loaded_texture_paths[path],
+            false => {
+                let tex = self.texture_creator.load_texture(path)?;
+                // reuse a slot freed by `unload` if there is one
+                let index = match self.textures.iter().position(|t| t.is_none()) {
+                    Some(i) => {
+                        self.textures[i] = Some(tex);
+                        i
+                    },
+                    None => {
+                        self.textures.push(Some(tex));
+                        self.ref_counts.push(0);
+                        self.textures.len() - 1
+                    },
+                };
+                self.loaded_texture_paths.insert(path.to_string(), index);
+
+                println!("loaded: {}", path);
+
+                index
+            },
+        };
+        self.ref_counts[tex_index] += 1;
+        let last_tex = self.textures[tex_index].as_ref().unwrap();
+        Ok(
+        resource::Texture {
+            id: tex_index,
+            width: last_tex.query().width,
+            height: last_tex.query().height,
+        })
+
+    }
+
+/// drop one reference to a texture, freeing it when nothing else has loaded it
+    pub fn unload(&mut self, path: &str) {
+        let tex_index = match self.loaded_texture_paths.get(path) {
+            Some(i) => *i,
+            None => { return; },
+        };
+        self.ref_counts[tex_index] -= 1;
+        if self.ref_counts[tex_index] == 0 {
+            self.textures[tex_index] = None;
+            self.loaded_texture_paths.remove(path);
+        }
+    }
+/// read a loaded texture's file again, in the same slot so existing `resource::Texture`s draw the new image
+    pub fn reload(&mut self, path: &str) -> Result<(), String> {
+        let tex_index = match self.loaded_texture_paths.get(path) {
+            Some(i) => *i,
+            None => { return Ok(()); },
+        };
+        self.textures[tex_index] = Some(self.texture_creator.load_texture(path)?);
+        Ok(())
+    }
+/// draw a `GameObject` to the canvas
+    pub fn draw(&mut self, canvas : &mut Canvas<Window>, tex_draw: TextureDraw) -> Result<(), String> {
+        let texture = match self.textures[tex_draw.tex.id].as_mut() {
+            Some(t) => t,
+            None => { return Err(String::from("tried to draw a texture that was unloaded")); },
+        };
+        texture.set_color_mod(
+            tex_draw.colour.r,
+            tex_draw.colour.g,
+            tex_draw.colour.b
+        );
+        texture.set_alpha_mod(tex_draw.colour.a);
+        let mut h_f = false;
+        let mut v_f = false;
+        let mut t_r = tex_draw.tex_rect;
+        if tex_draw.tex_rect.w < 0.0 {
+            h_f = true;
+            t_r.w *= -1.0;
+        }
+        if tex_draw.tex_rect.h < 0.0 {
+            v_f = true;
+            t_r.h *= -1.0;
+        }
+        canvas.copy_ex(
+            texture,
+            t_r.to_sdl_rect(),
+            tex_draw.draw_rect.to_sdl_rect(),
+            tex_draw.rotation,
+            None,
+            h_f,
+            v_f,
+        )
+    }
+
+    pub fn draw_rect(&self, canvas : &mut Canvas<Window>, rect : geometry::Rect, colour :  Colour) -> Result<(), String> {
+        canvas.set_draw_color(Color::RGBA(colour.r, colour.g, colour.b, colour.a));
+        canvas.fill_rect(rect.to_sdl_rect())?;
+        Ok(())
+    }
+}
+
+/// the texture and font managers together, so the game can load both through one `TextureLoader`
+pub struct Resources<'r, 'a, 'f, T> {
+    pub textures : &'r mut TextureManager<'a, T>,
+    pub fonts    : &'r mut FontManager<'f, T>,
+}
+
+impl<'r, 'a, 'f, T> TextureLoader for Resources<'r, 'a, 'f, T> {
+    fn load(&mut self, path: &str) -> Result<resource::Texture, String> {
+        self.textures.load(path)
+    }
+    fn unload(&mut self, path: &str) {
+        self.textures.unload(path)
+    }
+    fn reload(&mut self, path: &str) -> Result<(), String> {
+        self.textures.reload(path)
+    }
+    fn load_font(&mut self, family: &str) -> Result<resource::Font, String> {
+        self.fonts.load_family(family)
+    }
+}
+
+/// can be returned by `FontManager`, stores an sdl2 texture and a rect for drawing to a canvas
+pub struct TextDraw<'a> {
+    pub tex  : sdl2::render::Texture<'a>,
+    pub rect : sdl2::rect::Rect,
+}
+
+const FONT_LOAD_SIZE : u16 = 128;
+/// used for text whose font family has no `fonts/<family>.ttf`
+const DEFAULT_FONT : &str = "fonts/DejaVuSans.ttf";
+
+/// Stores 'sdl2::ttf::Font' and returns textures or draws them
+pub struct FontManager<'a, T> {
+    texture_creator : &'a TextureCreator<T>,
+    ttf_context: &'a ttf::Sdl2TtfContext,
+    loaded_font_paths : HashMap<String, usize>,
+    loaded_families : HashMap<String, resource::Font>,
+    pub fonts : Vec<ttf::Font<'a, 'static>>,
+}
+
+impl<'a, T> FontManager<'a, T> {
+    pub fn new(ttf_context : &'a ttf::Sdl2TtfContext, texture_creator : &'a TextureCreator<T>) -> Result<Self, String> {
+        Ok(FontManager {
+            texture_creator,
+            ttf_context,
+            loaded_font_paths: HashMap::new(),
+            loaded_families: HashMap::new(),
+            fonts : Vec::new(),
+        })
+    }
+
+    pub fn load_font(&mut self, path : &Path) -> Result<resource::Font, String>{
+        let path_string = path.to_string_lossy().to_string();
+        let font_index = match self.loaded_font_paths.contains_key(&path_string) {
+            true => self.loaded_font_paths[&path_string],
+            false => {
+                self.fonts.push(
+                    match self.ttf_context.load_font(path, FONT_LOAD_SIZE) {
+                        Ok(s) => s,
+                        Err(e) => { return Err(e.to_string()); }
+                    }
+                );
+                self.loaded_font_paths.insert(path_string, self.fonts.len() - 1);
+                self.fonts.len() - 1
+            }
+        };
+        Ok(
+            resource::Font {
+            id: font_index,
+        })
+    }
+    /// return a `TextDraw` that has a corrected `rect.width` based on the supplied height and the rendered font
+    pub fn get_draw(&self, font: &resource::Font, text: &str, height : u32, colour : Color) -> Result<TextDraw, String> {
+        self.get_draw_at_vec2(font, text, height, Vec2::new(0.0, 0.0), colour)
+    }
+
+    pub fn get_draw_at_vec2(&self, font: &resource::Font, text: &str, height : u32, pos: Vec2, colour: Color) -> Result<TextDraw, String> {
+        if text.len() == 0 { Err("text length should be greater than 0")?; }
+        let surface = match self.fonts[font.id]
+            .render(text)
+            .blended(colour) {
+                Ok(s) => s,
+                Err(e) => return Err(e.to_string()),
+        };
+        let tex = match self.texture_creator.create_texture_from_surface(&surface) {
+            Ok(t) => t,
+            Err(e) => { return Err(e.to_string()); },
+        };
+        let ratio = tex.query().height as f64 / tex.query().width as f64;
+        Ok(
+        TextDraw {
+            tex,
+            rect:
+             sdl2::rect::Rect::new(
+                pos.x as i32,
+                pos.y as i32,
+                (height as f64 / ratio) as u32,
+                height
+             ),
+        })
+    }
+
+    /// draws the supplied text to the canvas in the supplied font at the given height and position
+    pub fn draw(&self, canvas : &mut Canvas<Window>, font : &resource::Font, text: &str, height : u32, pos : Vec2, colour : Color) -> Result<(), String> {
+        if text.len() == 0 { return Ok(()); }
+        let mut tex_draw = self.get_draw(font, text, height, colour)?;
+        tex_draw.rect.x = pos.x as i32;
+        tex_draw.rect.y = pos.y as i32;
+        canvas.copy(&tex_draw.tex, None, tex_draw.rect)
+    }
+
+    /// load the font for a family name, from `fonts/<family>.ttf` if there is one
+    pub fn load_family(&mut self, family : &str) -> Result<resource::Font, String> {
+        if let Some(font) = self.loaded_families.get(family) {
+            return Ok(*font);
+        }
+        let path = format!("fonts/{}.ttf", family);
+        let font = if Path::new(&path).exists() {
+            self.load_font(Path::new(&path))?
+        } else {
+            self.load_font(Path::new(DEFAULT_FONT))?
+        };
+        self.loaded_families.insert(family.to_string(), font);
+        Ok(font)
+    }
+
+    /// wrap, align and draw a `TextBox` whose rect is already in window coordinates
+    pub fn draw_text_box(&mut self, canvas : &mut Canvas<Window>, text_box : &TextBox) -> Result<(), String> {
+        let height = text_box.pixel_size.round() as u32;
+        if height == 0 { return Ok(()); }
+        let font = match text_box.font {
+            Some(font) => font,
+            None => self.load_family(&text_box.font_family)?,
+        };
+        let mut style = ttf::FontStyle::NORMAL;
+        if text_box.bold { style |= ttf::FontStyle::BOLD; }
+        if text_box.italic { style |= ttf::FontStyle::ITALIC; }
+        self.fonts[font.id].set_style(style);
+        let result = self.draw_styled(canvas, &font, text_box, height);
+        self.fonts[font.id].set_style(ttf::FontStyle::NORMAL);
+        result
+    }
+
+    fn draw_styled(&self, canvas : &mut Canvas<Window>, font : &resource::Font, text_box : &TextBox, height : u32) -> Result<(), String> {
+        let ttf_font = &self.fonts[font.id];
+        let scale = text_box.pixel_size / ttf_font.height() as f64;
+        let pieces = text::layout(text_box, |s| match ttf_font.size_of(s) {
+            Ok((w, _)) => w as f64 * scale,
+            Err(_) => 0.0,
+        });
+        for p in pieces {
+            let mut draw = self.get_draw_at_vec2(font, &p.text, height, p.pos, text_box.colour.to_sdl2_colour())?;
+            draw.tex.set_alpha_mod(text_box.colour.a);
+            canvas.copy(&draw.tex, None, draw.rect)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,120 @@
+//! text placed in the world, laid out into lines by whichever frontend has the font to measure it
+
+use geometry::{Rect, Vec2};
+use crate::{resource, Colour};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HorizontalAlign {
+    Left,
+    Centre,
+    Right,
+    /// spread the words of each wrapped line to fill the width, the last line of a paragraph stays left
+    Justify,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum VerticalAlign {
+    Top,
+    Centre,
+    Bottom,
+}
+
+/// a block of text to be fitted inside `rect`
+#[derive(Clone)]
+pub struct TextBox {
+    pub text: String,
+    pub font_family: String,
+    /// the font for `font_family`, set when the map loads so drawing doesn't have to look it up
+    pub font: Option<resource::Font>,
+    /// the height of a line of text
+    pub pixel_size: f64,
+    pub rect: Rect,
+    pub parallax: Vec2,
+    pub colour: Colour,
+    /// break lines that are wider than `rect`, lines always break at newlines
+    pub wrap: bool,
+    pub bold: bool,
+    pub italic: bool,
+    pub horizontal_align: HorizontalAlign,
+    pub vertical_align: VerticalAlign,
+}
+
+impl TextBox {
+    pub fn new(text: &str, rect: Rect, pixel_size: f64) -> Self {
+        TextBox {
+            text: text.to_string(),
+            font_family: String::from("sans-serif"),
+            font: None,
+            pixel_size,
+            rect,
+            parallax: Vec2::new(1.0, 1.0),
+            colour: Colour::black(),
+            wrap: false,
+            bold: false,
+            italic: false,
+            horizontal_align: HorizontalAlign::Left,
+            vertical_align: VerticalAlign::Top,
+        }
+    }
+}
+
+/// a run of text and the top left corner to draw it at
+#[derive(Clone)]
+pub struct TextPiece {
+    pub text: String,
+    pub pos: Vec2,
+}
+
+/// split a `TextBox` into pieces that each fit on one line, `measure` gives
+/// the width of a string drawn at the box's `pixel_size`
+pub fn layout<F: Fn(&str) -> f64>(text: &TextBox, measure: F) -> Vec<TextPiece> {
+    let space = measure(" ");
+    // each line and whether it ends a paragraph
+    let mut lines: Vec<(Vec<&str>, bool)> = Vec::new();
+    for paragraph in text.text.lines() {
+        let mut line: Vec<&str> = Vec::new();
+        let mut width = 0.0;
+        for word in paragraph.split(' ').filter(|w| !w.is_empty()) {
+            let word_width = measure(word);
+            if text.wrap && !line.is_empty() && width + space + word_width > text.rect.w {
+                lines.push((line, false));
+                line = Vec::new();
+                width = 0.0;
+            }
+            if !line.is_empty() { width += space; }
+            width += word_width;
+            line.push(word);
+        }
+        lines.push((line, true));
+    }
+
+    let height = lines.len() as f64 * text.pixel_size;
+    let mut y = text.rect.y + match text.vertical_align {
+        VerticalAlign::Top => 0.0,
+        VerticalAlign::Centre => (text.rect.h - height) / 2.0,
+        VerticalAlign::Bottom => text.rect.h - height,
+    };
+    let mut pieces = Vec::new();
+    for (words, last) in lines.iter() {
+        if text.horizontal_align == HorizontalAlign::Justify && !last && words.len() > 1 {
+            let widths: Vec<f64> = words.iter().map(|w| measure(w)).collect();
+            let gap = (text.rect.w - widths.iter().sum::<f64>()) / (words.len() - 1) as f64;
+            let mut x = text.rect.x;
+            for (word, width) in words.iter().zip(widths) {
+                pieces.push(TextPiece { text: word.to_string(), pos: Vec2::new(x, y) });
+                x += width + gap;
+            }
+        } else if !words.is_empty() {
+            let line = words.join(" ");
+            let width = measure(&line);
+            let x = text.rect.x + match text.horizontal_align {
+                HorizontalAlign::Left | HorizontalAlign::Justify => 0.0,
+                HorizontalAlign::Centre => (text.rect.w - width) / 2.0,
+                HorizontalAlign::Right => text.rect.w - width,
+            };
+            pieces.push(TextPiece { text: line, pos: Vec2::new(x, y) });
+        }
+        y += text.pixel_size;
+    }
+    pieces
+}
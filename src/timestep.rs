@@ -0,0 +1,51 @@
+//! accumulate real frame time and hand it out in fixed sized physics steps
+
+/// length of one physics step in seconds
+pub const STEP: f64 = 1.0 / 120.0;
+
+/// frames longer than this are cut short, so a hitch doesn't trigger a burst of catch up steps
+const MAX_FRAME: f64 = 0.25;
+
+/// stops float error in the accumulator from dropping or adding a step
+const EPSILON: f64 = 1e-9;
+
+pub struct FixedStep {
+    step: f64,
+    acc: f64,
+    steps: u64,
+}
+
+impl FixedStep {
+    pub fn new(step: f64) -> FixedStep {
+        FixedStep {
+            step,
+            acc: 0.0,
+            steps: 0,
+        }
+    }
+
+    /// add the real time the last frame took
+    pub fn add_frame(&mut self, frame: f64) {
+        self.acc += frame.min(MAX_FRAME);
+    }
+
+    /// returns true and consumes a step if there is enough time built up for one
+    pub fn step(&mut self) -> bool {
+        if self.acc + EPSILON < self.step {
+            return false;
+        }
+        self.acc = (self.acc - self.step).max(0.0);
+        self.steps += 1;
+        true
+    }
+
+    /// how far between the last step and the next the current frame is, from 0 to 1
+    pub fn alpha(&self) -> f64 {
+        (self.acc / self.step).min(1.0)
+    }
+
+    /// total steps taken so far
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+}
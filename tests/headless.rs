@@ -0,0 +1,481 @@
+use coupled_explorers::{TextureLoader, input::Input, game::{Game, Players}, replay::Replay};
+use coupled_explorers::camera::{Camera, CamDraw};
+use coupled_explorers::text::{TextBox, HorizontalAlign, VerticalAlign, layout};
+use coupled_explorers::headless::{Headless, HeadlessTextures};
+use coupled_explorers::timestep::{FixedStep, STEP};
+use coupled_explorers::hot_reload::Watcher;
+use geometry::{Rect, Vec2};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// a headless frontend on the levels in `manifest`, with the same view and window as the game
+fn headless_for(manifest: &str) -> Headless {
+    let mut textures = HeadlessTextures::new();
+    let game = Game::new_from_manifest(manifest, &mut textures).unwrap();
+    Headless {
+        game,
+        cam: Camera::new(Rect::new(0.0, 0.0, 240.0, 160.0), Vec2::new(720.0, 480.0)),
+        timestep: FixedStep::new(STEP),
+        textures,
+    }
+}
+
+fn run(steps: usize, input: &Input) -> Headless {
+    let mut h = Headless::new().unwrap();
+    for _ in 0..steps {
+        h.step(input).unwrap();
+    }
+    h
+}
+
+fn run_frames(seconds: f64, fps: f64, input: &Input) -> Headless {
+    let mut h = Headless::new().unwrap();
+    let frames = (seconds * fps).round() as usize;
+    for _ in 0..frames {
+        h.frame(1.0 / fps, input).unwrap();
+    }
+    h
+}
+
+#[test]
+fn loads_all_levels() {
+    let h = Headless::new().unwrap();
+    assert!(h.game.level() == 0);
+    assert!(!h.game.game_complete());
+}
+
+#[test]
+fn same_inputs_same_trajectory() {
+    let mut input = Input::new();
+    input.right = true;
+    input.a = true;
+    let a = run(480, &input);
+    let b = run(480, &input);
+    for p in [Players::Heavy, Players::Quick] {
+        assert!(a.game.player_rect(p).x == b.game.player_rect(p).x);
+        assert!(a.game.player_rect(p).y == b.game.player_rect(p).y);
+    }
+}
+
+#[test]
+fn holding_right_moves_player() {
+    let start = run(0, &Input::new());
+    let mut input = Input::new();
+    input.right = true;
+    let moved = run(120, &input);
+    assert!(moved.game.current_player() == Players::Heavy);
+    assert!(moved.game.player_rect(Players::Heavy).x > start.game.player_rect(Players::Heavy).x);
+}
+
+#[test]
+fn frame_rate_does_not_change_trajectory() {
+    let mut input = Input::new();
+    input.right = true;
+    input.a = true;
+    let slow = run_frames(3.0, 30.0, &input);
+    let fast = run_frames(3.0, 240.0, &input);
+    assert!(slow.timestep.steps() == fast.timestep.steps());
+    for p in [Players::Heavy, Players::Quick] {
+        assert!(slow.game.player_rect(p).x == fast.game.player_rect(p).x);
+        assert!(slow.game.player_rect(p).y == fast.game.player_rect(p).y);
+    }
+}
+
+#[test]
+fn replay_reproduces_run() {
+    let mut recorded = Headless::new().unwrap();
+    let mut replay = Replay::new(recorded.game.level(), 0);
+    for i in 0..600 {
+        let mut input = Input::new();
+        input.right = i % 200 < 150;
+        input.a = i % 90 < 20;
+        input.debug_1 = i == 300;
+        replay.record(&input);
+        recorded.step(&input).unwrap();
+    }
+    assert!(replay.len() == 600);
+
+    let loaded = Replay::from_text(&replay.to_text()).unwrap();
+    let mut played = Headless::new().unwrap();
+    played.play(&loaded).unwrap();
+    assert!(played.game.current_player() == recorded.game.current_player());
+    for p in [Players::Heavy, Players::Quick] {
+        assert!(played.game.player_rect(p).x == recorded.game.player_rect(p).x);
+        assert!(played.game.player_rect(p).y == recorded.game.player_rect(p).y);
+    }
+}
+
+#[test]
+fn replay_starts_on_recorded_level() {
+    let mut replay = Replay::new(2, 0);
+    replay.record(&Input::new());
+    let mut h = Headless::new().unwrap();
+    h.play(&Replay::from_text(&replay.to_text()).unwrap()).unwrap();
+    assert!(h.game.level() == 2);
+}
+
+#[test]
+fn levels_come_from_manifest() {
+    let h = Headless::new().unwrap();
+    assert!(h.game.level_count() == 6);
+    assert!(h.game.level_info().title == "Level 1");
+    assert!(h.game.level_info().switch_time == 10.0);
+}
+
+#[test]
+fn next_level_streams_in() {
+    let mut h = Headless::new().unwrap();
+    let mut input = Input::new();
+    input.debug_2 = true;
+    h.step(&input).unwrap();
+    assert!(h.game.level() == 0);
+    h.step(&Input::new()).unwrap();
+    assert!(h.game.level() == 1);
+    assert!(h.textures.is_loaded("textures/tileset.png"));
+}
+
+#[test]
+fn textures_unload_when_last_user_releases() {
+    let mut textures = HeadlessTextures::new();
+    textures.load("textures/end.png").unwrap();
+    textures.load("textures/end.png").unwrap();
+    textures.unload("textures/end.png");
+    assert!(textures.is_loaded("textures/end.png"));
+    textures.unload("textures/end.png");
+    assert!(!textures.is_loaded("textures/end.png"));
+}
+
+#[test]
+fn image_layers_repeat_across_the_view() {
+    let mut h = headless_for("tests/maps/levels.toml");
+    let sky = h.textures.load("textures/end.png").unwrap();
+    h.step(&Input::new()).unwrap();
+    h.cam.set_offset(Vec2::new(100.0, 0.0));
+    h.game.draw(&mut h.cam, 1.0);
+    let skies: Vec<Rect> = h.cam.drain_draws().filter_map(|d| match d {
+        CamDraw::Tex(t) if t.tex.id == sky.id => Some(t.draw_rect),
+        _ => None,
+    }).collect();
+    assert!(skies.len() == 2);
+    assert!(skies.iter().any(|r| r.x <= 0.0));
+    assert!(skies.iter().any(|r| r.x + r.w >= 720.0));
+}
+
+#[test]
+fn text_objects_are_drawn() {
+    let mut h = headless_for("tests/maps/levels.toml");
+    h.game.draw(&mut h.cam, 1.0);
+    let texts: Vec<TextBox> = h.cam.drain_draws().filter_map(|d| match d {
+        CamDraw::Text(t) => Some(t),
+        _ => None,
+    }).collect();
+    assert!(texts.len() == 1);
+    assert!(texts[0].text == "press Z to jump");
+    assert!(texts[0].wrap);
+    assert!(texts[0].horizontal_align == HorizontalAlign::Centre);
+    // tiled's default text colour, on a layer with no tint
+    let c = texts[0].colour;
+    assert!((c.r, c.g, c.b, c.a) == (0, 0, 0, 255));
+    // scaled from world to window size
+    assert!(texts[0].pixel_size == 24.0);
+}
+
+#[test]
+fn text_wraps_and_aligns() {
+    // every character is 1 wide at this size
+    let measure = |s: &str| s.chars().count() as f64;
+    let mut text = TextBox::new("press Z to jump", Rect::new(0.0, 0.0, 10.0, 30.0), 5.0);
+    text.wrap = true;
+    let pieces = layout(&text, measure);
+    let lines: Vec<&str> = pieces.iter().map(|p| p.text.as_str()).collect();
+    assert!(lines == ["press Z to", "jump"]);
+    assert!(pieces[1].pos.y == 5.0);
+
+    text.horizontal_align = HorizontalAlign::Right;
+    text.vertical_align = VerticalAlign::Bottom;
+    let pieces = layout(&text, measure);
+    assert!(pieces[1].pos.x == 6.0);
+    assert!(pieces[1].pos.y == 25.0);
+
+    text.horizontal_align = HorizontalAlign::Justify;
+    text.text = String::from("a b c d\nend");
+    text.rect.w = 5.0;
+    let pieces = layout(&text, measure);
+    let xs: Vec<f64> = pieces.iter().map(|p| p.pos.x).collect();
+    assert!(xs == [0.0, 2.0, 4.0, 0.0, 0.0]);
+
+    text.wrap = false;
+    text.horizontal_align = HorizontalAlign::Centre;
+    let pieces = layout(&text, measure);
+    assert!(pieces[0].text == "a b c d");
+    assert!(pieces[0].pos.x == -1.0);
+}
+
+#[test]
+fn broken_map_reports_where() {
+    let mut textures = HeadlessTextures::new();
+    let err = match Game::new_from_manifest("tests/maps/broken.toml", &mut textures) {
+        Err(e) => e,
+        Ok(_) => panic!("a broken map should not load"),
+    };
+    assert!(err.contains("tests/maps/broken.tmx:4:2: in layer"));
+}
+
+/// an empty directory for a test to write files into
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("coupled_explorers_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// move a file's modification time forward, so the change is seen however coarse the file system's clock is
+fn touch(path: &Path, seconds: u64) {
+    let file = fs::File::options().write(true).open(path).unwrap();
+    file.set_modified(SystemTime::now() + Duration::from_secs(seconds)).unwrap();
+}
+
+#[test]
+fn watcher_reports_changed_files() {
+    let dir = scratch_dir("watch");
+    let old = dir.join("old.tmx");
+    fs::write(&old, "old").unwrap();
+    let mut watcher = Watcher::new(&[dir.to_str().unwrap()], Duration::ZERO);
+    assert!(watcher.changed().is_empty());
+
+    touch(&old, 10);
+    assert!(watcher.changed() == [old.to_string_lossy()]);
+    assert!(watcher.changed().is_empty());
+
+    let new = dir.join("sub").join("new.tsx");
+    fs::create_dir_all(new.parent().unwrap()).unwrap();
+    fs::write(&new, "new").unwrap();
+    assert!(watcher.changed() == [new.to_string_lossy()]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn editing_the_map_restarts_the_level() {
+    let dir = scratch_dir("reload");
+    // tiled paths are relative, so climb out of the scratch dir back to the repo
+    let up = "../".repeat(dir.components().count() - 1);
+    let root = format!("{}{}/", up, env!("CARGO_MANIFEST_DIR").trim_start_matches('/'));
+    let sky = fs::read_to_string("tests/maps/sky.tmx").unwrap().replace("../../", &root);
+    let map = dir.join("sky.tmx");
+    fs::write(&map, &sky).unwrap();
+    fs::write(dir.join("levels.toml"), "[[level]]\nmap = \"sky.tmx\"\n").unwrap();
+    let manifest = dir.join("levels.toml");
+    let mut textures = HeadlessTextures::new();
+    let mut game = Game::new_from_manifest(manifest.to_str().unwrap(), &mut textures).unwrap();
+    assert!(game.player_rect(Players::Heavy).x == 10.0);
+
+    fs::write(&map, sky.replace("<object id=\"1\" x=\"10\"", "<object id=\"1\" x=\"30\"")).unwrap();
+    game.hot_reload(&[map.to_string_lossy().to_string()], &mut textures).unwrap();
+    assert!(game.player_rect(Players::Heavy).x == 30.0);
+
+    // a half saved map keeps the old one playable
+    fs::write(&map, "<map").unwrap();
+    assert!(game.hot_reload(&[map.to_string_lossy().to_string()], &mut textures).is_err());
+    assert!(game.player_rect(Players::Heavy).x == 30.0);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn textures_reload_in_place() {
+    let dir = scratch_dir("textures");
+    let path = dir.join("sprite.png");
+    let path = path.to_str().unwrap();
+    fs::copy("textures/end.png", path).unwrap();
+    let mut textures = HeadlessTextures::new();
+    let before = textures.load(path).unwrap();
+    fs::copy("textures/quick.png", path).unwrap();
+    textures.reload(path).unwrap();
+    let after = textures.load(path).unwrap();
+    let quick = textures.load("textures/quick.png").unwrap();
+    assert!(after.id == before.id);
+    assert!(after.width == quick.width && after.height == quick.height);
+    assert!(after.width != before.width);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn isometric_tiles_overlap_in_draw_order() {
+    let mut h = headless_for("tests/maps/iso.toml");
+    let tileset = h.textures.load("textures/tileset.png").unwrap();
+    h.game.draw(&mut h.cam, 1.0);
+    let tiles: Vec<Rect> = h.cam.drain_draws().filter_map(|d| match d {
+        CamDraw::Tex(t) if t.tex.id == tileset.id => Some(t.draw_rect),
+        _ => None,
+    }).collect();
+    // the top tile first, then the two beside each other, then the bottom one, 3 window pixels to a world pixel,
+    // then the tile object standing on the right tile's bottom corner by its bottom centre, right over that tile
+    let offsets: Vec<(f64, f64)> = tiles.iter().map(|r| ((r.x - tiles[0].x) / 3.0, (r.y - tiles[0].y) / 3.0)).collect();
+    assert!(offsets == [(0.0, 0.0), (10.0, 5.0), (-10.0, 5.0), (0.0, 10.0), (10.0, 5.0)]);
+}
+
+#[test]
+fn isometric_objects_are_projected_onto_the_tiles() {
+    let h = headless_for("tests/maps/iso.toml");
+    // the spawn is a tile along the map's x axis from the top corner, so at the top of the tile at 1, 0
+    let spawn = h.game.player_rect(Players::Heavy);
+    assert!((spawn.x, spawn.y) == (30.0, 5.0));
+    // a rect over the tile at 1, 1 becomes its diamond, inside the tile's 20 by 10 box
+    let rects = h.game.object_rects();
+    assert!(rects.iter().any(|r| (r.x, r.y, r.w, r.h) == (10.0, 10.0, 20.0, 10.0)));
+}
+
+#[test]
+fn tile_objects_draw_from_their_bottom_left() {
+    let mut h = headless_for("tests/maps/sprites.toml");
+    let tileset = h.textures.load("textures/tileset.png").unwrap();
+    h.game.draw(&mut h.cam, 1.0);
+    let sprites: Vec<(Rect, Rect, f64)> = h.cam.drain_draws().filter_map(|d| match d {
+        CamDraw::Tex(t) if t.tex.id == tileset.id => Some((t.draw_rect, t.tex_rect, t.rotation)),
+        _ => None,
+    }).collect();
+    assert!(sprites.len() == 3);
+    // relative to the first sprite, which covers 40, 40 to 60, 60 in the world
+    let (first, _, _) = sprites[0];
+    let placed: Vec<(f64, f64, f64, f64, f64)> = sprites.iter().map(|(r, _, rotation)| (
+        (r.x - first.x) / 3.0, (r.y - first.y) / 3.0, r.w / 3.0, r.h / 3.0, *rotation,
+    )).collect();
+    // turning around the bottom left corner swings the second sprite down below its corner
+    assert!(placed == [
+        (0.0, 0.0, 20.0, 20.0, 0.0),
+        (60.0, 20.0, 20.0, 20.0, 90.0),
+        (100.0, 50.0, 40.0, 10.0, 0.0),
+    ]);
+    // the second is flipped horizontally and all show tile 3
+    assert!(sprites[1].1.w == -20.0);
+    assert!(sprites.iter().all(|(_, tex, _)| (tex.x, tex.y) == (40.0, 0.0)));
+}
+
+#[test]
+fn polygons_and_ellipses_collide_by_their_outline() {
+    let mut h = headless_for("tests/maps/shapes.toml");
+    for _ in 0..600 {
+        h.step(&Input::new()).unwrap();
+    }
+    let rects = h.game.object_rects();
+    let find = |w: f64, h: f64| *rects.iter().find(|r| r.w == w && r.h == h).unwrap();
+    // the ramp's rect reaches up to 80, but the boulder falls until it touches the slope further down
+    let boulder = find(20.0, 20.0);
+    assert!(boulder.y > 95.0 && boulder.y < 105.0);
+    // the box drops into the notch in the middle of the concave polygon rather than landing on its rim
+    let b = find(16.0, 10.0);
+    assert!((b.y - 120.0).abs() < 0.5);
+}
+
+#[test]
+fn players_walk_up_and_down_slopes() {
+    let mut h = headless_for("tests/maps/slopes.toml");
+    // the ramp rises from 60, 140 to 160, 90 then is flat until 220
+    let ramp = |x: f64| 140.0 - (x.clamp(60.0, 160.0) - 60.0) / 2.0;
+    let feet = |h: &Headless| {
+        let r = h.game.player_rect(Players::Heavy);
+        (r.x + r.w / 2.0, r.y + r.h)
+    };
+    let on_ramp = |h: &Headless| {
+        let (x, y) = feet(h);
+        (y - ramp(x)).abs() < 0.5
+    };
+    for _ in 0..120 {
+        h.step(&Input::new()).unwrap();
+    }
+    let mut input = Input::new();
+    input.right = true;
+    for _ in 0..300 {
+        h.step(&input).unwrap();
+        let (x, _) = feet(&h);
+        if x > 62.0 && x < 215.0 {
+            assert!(on_ramp(&h));
+        }
+        if x > 190.0 { break; }
+    }
+    assert!(feet(&h).0 > 190.0);
+
+    // walking back down it stays on the slope rather than running off into the air
+    input.right = false;
+    input.left = true;
+    for _ in 0..300 {
+        h.step(&input).unwrap();
+        let (x, _) = feet(&h);
+        if x < 100.0 { break; }
+        assert!(on_ramp(&h));
+    }
+    assert!(feet(&h).0 < 100.0);
+
+    // the slope counts as ground to jump from
+    input.left = false;
+    input.a = true;
+    h.step(&input).unwrap();
+    assert!(h.game.jumped() == Some(Players::Heavy));
+    for _ in 0..10 {
+        h.step(&input).unwrap();
+    }
+    let (x, y) = feet(&h);
+    assert!(y < ramp(x) - 5.0);
+}
+
+#[test]
+fn tile_collision_shapes_are_solid_and_flip_with_the_tile() {
+    let mut h = headless_for("tests/maps/tile_shapes.toml");
+    for _ in 0..600 {
+        h.step(&Input::new()).unwrap();
+    }
+    let rects = h.game.object_rects();
+    let bottom = |x: f64| {
+        let r = rects.iter().find(|r| r.x == x && r.w == 4.0).unwrap();
+        r.y + r.h
+    };
+    // the triangle rises to the right, so a box near its left lands low on the slope
+    assert!((bottom(42.0) - 154.0).abs() < 1.0);
+    // flipped, it rises to the left, so the same spot is near its top
+    assert!((bottom(102.0) - 142.0).abs() < 1.0);
+    // the ellipse curves away below the top of its tile, a box over its left edge catches on it at 144
+    assert!((bottom(158.0) - 144.0).abs() < 1.0);
+}
+
+#[test]
+fn hidden_groups_are_not_drawn_or_solid() {
+    let mut h = headless_for("tests/maps/hidden.toml");
+    let tileset = h.textures.load("textures/tileset.png").unwrap();
+    h.game.draw(&mut h.cam, 1.0);
+    let tiles = h.cam.drain_draws().filter(|d| matches!(d, CamDraw::Tex(t) if t.tex.id == tileset.id)).count();
+    assert!(tiles == 1);
+    for _ in 0..600 {
+        h.step(&Input::new()).unwrap();
+    }
+    // the box over the hidden tile falls out of the level, the one over the shown tile lands on it
+    let rects = h.game.object_rects();
+    assert!(!rects.iter().any(|r| r.x == 52.0));
+    assert!(rects.iter().any(|r| r.x == 112.0 && r.y + r.h < 160.0));
+}
+
+#[test]
+fn objects_follow_their_groups_offsets() {
+    let mut h = headless_for("tests/maps/offset.toml");
+    let spawn = h.game.player_rect(Players::Heavy);
+    assert!((spawn.x, spawn.y) == (90.0, 70.0));
+    for _ in 0..600 {
+        h.step(&Input::new()).unwrap();
+    }
+    let rects = h.game.object_rects();
+    // the ledge is moved by both the group and its layer, so the box over where it ends up lands on it
+    assert!(rects.iter().any(|r| (r.x, r.y, r.w, r.h) == (60.0, 100.0, 40.0, 20.0)));
+    assert!(rects.iter().any(|r| r.x == 62.0 && (r.y + r.h - 100.0).abs() < 0.5));
+    // the hidden and parallax ledges aren't there to catch theirs
+    assert!(!rects.iter().any(|r| r.x == 132.0 || r.x == 172.0));
+}
+
+#[test]
+fn map_warnings_are_handed_to_the_caller() {
+    let mut h = headless_for("tests/maps/warnings.toml");
+    let warnings = h.game.take_warnings();
+    assert!(warnings.len() == 2);
+    assert!(warnings[0].message.contains("shiny"));
+    assert!(warnings[1].message.contains("sparkles"));
+    assert!(h.game.take_warnings().is_empty());
+}
@@ -0,0 +1,18 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.8" tiledversion="1.8.2" name="shapes" tilewidth="20" tileheight="20" tilecount="320" columns="10">
+ <image source="../../textures/tileset.png" width="200" height="640"/>
+ <tile id="0">
+  <objectgroup draworder="index" id="2">
+   <object id="1" x="0" y="0">
+    <polygon points="0,20 20,20 20,0"/>
+   </object>
+  </objectgroup>
+ </tile>
+ <tile id="1">
+  <objectgroup draworder="index" id="2">
+   <object id="1" x="0" y="0" width="20" height="20">
+    <ellipse/>
+   </object>
+  </objectgroup>
+ </tile>
+</tileset>